@@ -1,150 +1,1144 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use color_eyre::Result;
+use color_eyre::eyre::{eyre, Context, Report, Result};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::text::Text;
 use tui_tree_widget::TreeState;
 
-use crate::tmux::{self, TmuxSession};
+use crate::cli::{Args, StatusPosition, TreeSymbols, WindowSort};
+use crate::clipboard;
+use crate::store;
+use crate::template;
+use crate::tmux::{self, TmuxPane, TmuxSession};
 use crate::ui;
 
 /// Application mode
 #[derive(Debug, Clone)]
 pub enum Mode {
     Normal,
-    CreateSession { input: String },
-    RenameSession { target: String, input: String },
-    ConfirmKill { target: String },
+    CreateSession {
+        input: String,
+        /// Working directory for the new session, when duplicating one
+        start_dir: Option<String>,
+        /// Why the last Enter was refused, cleared by editing
+        error: Option<String>,
+    },
+    RenameSession {
+        target: String,
+        input: String,
+        /// Why the last Enter was refused, cleared by editing
+        error: Option<String>,
+        /// Edited in place on the session's tree row instead of in a popup
+        inline: bool,
+    },
+    ConfirmKill {
+        target: String,
+        /// What goes with the session, shown so a busy one isn't killed by mistake
+        windows: usize,
+        panes: usize,
+    },
+    /// Asking before `q`/Esc quits (`--confirm-quit`)
+    ConfirmQuit,
+    /// Kill-server prompt; `input` must read "yes" to proceed
+    ConfirmKillServer {
+        input: String,
+    },
+    /// Typing a command to send to pane (or window/session) `target`
+    SendKeys {
+        target: String,
+        input: String,
+    },
+    /// Editing the note shown next to session `target`
+    EditNote {
+        target: String,
+        input: String,
+    },
+    /// Error too long for the status bar, shown in a popup scrolled down `scroll` lines
+    Error {
+        message: String,
+        scroll: u16,
+    },
+    /// Session `session`'s environment, scrolled down `scroll` lines
+    Environment {
+        session: String,
+        vars: Vec<(String, String)>,
+        scroll: u16,
+    },
+    /// The last lines of pane `target`, as captured when opened or resized
+    Preview {
+        target: String,
+        text: String,
+    },
+    /// Searching pane commands and paths; `selected` indexes the matches
+    Search {
+        query: String,
+        selected: usize,
+    },
+    /// Picking a destination session for window `src` ("session:index"),
+    /// which is linked there instead of moved when `link` is set
+    MoveWindow {
+        src: String,
+        link: bool,
+    },
+    /// The `,` actions menu; `selected` indexes `MenuItem::ALL`
+    Menu {
+        selected: usize,
+    },
+}
+
+/// Actions offered by the `,` menu rather than each taking a key, which
+/// would shadow a session label (`a`-`z` select, `A`-`Z` attach)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    AttachReadOnly,
+    AttachInTerminal,
+    DuplicateSession,
+    EditNote,
+    DetachClients,
+    ShowEnvironment,
+    ExportTemplate,
+    MoveWindow,
+    LinkWindow,
+    RenumberWindows,
+    ToggleSync,
+    RespawnWindow,
+    BreakPane,
+    RespawnPane,
+    PreviewPane,
+    CapturePane,
+    CopyPath,
+    CopyAttachCommand,
+    SessionsOnly,
+    GroupSessions,
+    AttachedOnly,
+    AllPanes,
+    Processes,
+    Usage,
+    ServerInfo,
+    KillServer,
+}
+
+impl MenuItem {
+    /// Menu order: session, window, pane, view, then server actions
+    pub const ALL: [MenuItem; 26] = [
+        MenuItem::AttachReadOnly,
+        MenuItem::AttachInTerminal,
+        MenuItem::DuplicateSession,
+        MenuItem::EditNote,
+        MenuItem::DetachClients,
+        MenuItem::ShowEnvironment,
+        MenuItem::ExportTemplate,
+        MenuItem::MoveWindow,
+        MenuItem::LinkWindow,
+        MenuItem::RenumberWindows,
+        MenuItem::ToggleSync,
+        MenuItem::RespawnWindow,
+        MenuItem::BreakPane,
+        MenuItem::RespawnPane,
+        MenuItem::PreviewPane,
+        MenuItem::CapturePane,
+        MenuItem::CopyPath,
+        MenuItem::CopyAttachCommand,
+        MenuItem::SessionsOnly,
+        MenuItem::GroupSessions,
+        MenuItem::AttachedOnly,
+        MenuItem::AllPanes,
+        MenuItem::Processes,
+        MenuItem::Usage,
+        MenuItem::ServerInfo,
+        MenuItem::KillServer,
+    ];
+
+    /// Accelerator key within the menu
+    pub fn key(self) -> char {
+        match self {
+            MenuItem::AttachReadOnly => 'o',
+            MenuItem::AttachInTerminal => 'T',
+            MenuItem::DuplicateSession => 'D',
+            MenuItem::EditNote => 'e',
+            MenuItem::DetachClients => 'd',
+            MenuItem::ShowEnvironment => 'E',
+            MenuItem::ExportTemplate => 't',
+            MenuItem::MoveWindow => 'm',
+            MenuItem::LinkWindow => 'l',
+            MenuItem::RenumberWindows => 'n',
+            MenuItem::ToggleSync => 's',
+            MenuItem::RespawnWindow => 'X',
+            MenuItem::BreakPane => 'b',
+            MenuItem::RespawnPane => 'x',
+            MenuItem::PreviewPane => 'V',
+            MenuItem::CapturePane => 'c',
+            MenuItem::CopyPath => 'y',
+            MenuItem::CopyAttachCommand => 'Y',
+            MenuItem::SessionsOnly => 'z',
+            MenuItem::GroupSessions => 'v',
+            MenuItem::AttachedOnly => 'F',
+            MenuItem::AllPanes => 'P',
+            MenuItem::Processes => 'p',
+            MenuItem::Usage => 'U',
+            MenuItem::ServerInfo => 'i',
+            MenuItem::KillServer => 'K',
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuItem::AttachReadOnly => "Attach read-only",
+            MenuItem::AttachInTerminal => "Attach in a new terminal window",
+            MenuItem::DuplicateSession => "Duplicate session in its directory",
+            MenuItem::EditNote => "Edit the session's note",
+            MenuItem::DetachClients => "Detach all clients of the session",
+            MenuItem::ShowEnvironment => "Show session environment",
+            MenuItem::ExportTemplate => "Save session as a --load template",
+            MenuItem::MoveWindow => "Move window to another session",
+            MenuItem::LinkWindow => "Link window into another session",
+            MenuItem::RenumberWindows => "Renumber windows",
+            MenuItem::ToggleSync => "Toggle synchronize-panes",
+            MenuItem::RespawnWindow => "Respawn window (resets it to one pane)",
+            MenuItem::BreakPane => "Break pane into its own window",
+            MenuItem::RespawnPane => "Respawn dead pane",
+            MenuItem::PreviewPane => "Preview the pane's last lines",
+            MenuItem::CapturePane => "Save the pane's scrollback to a file",
+            MenuItem::CopyPath => "Copy the pane's directory",
+            MenuItem::CopyAttachCommand => "Copy an attach command",
+            MenuItem::SessionsOnly => "Toggle session rows only",
+            MenuItem::GroupSessions => "Toggle grouping by name prefix",
+            MenuItem::AttachedOnly => "Toggle hiding detached sessions",
+            MenuItem::AllPanes => "Toggle listing single panes",
+            MenuItem::Processes => "Toggle pane PIDs and command lines",
+            MenuItem::Usage => "Toggle pane CPU and memory",
+            MenuItem::ServerInfo => "Show server info",
+            MenuItem::KillServer => "Kill tmux server",
+        }
+    }
+}
+
+/// Persist `session` as the one last attached to from tmxu, marked `[last]`
+pub fn save_last_attached(session: &str) {
+    // tmxu is about to exit, so there's no status bar left to report to
+    if let Err(e) = store::save(LAST_ATTACHED_FILE, &Some(session)) {
+        log::warn!("failed to save last attached session: {e}");
+    }
+}
+
+/// Where and how to attach once the TUI exits
+#[derive(Debug)]
+pub struct AttachRequest {
+    pub target: String,
+    /// Attach as a read-only client (`attach-session -r`)
+    pub read_only: bool,
+    /// Run before attaching, since attach only picks a session
+    pub steps: Vec<PreAttach>,
+}
+
+impl AttachRequest {
+    /// Session the attach lands in
+    pub fn session(&self) -> &str {
+        self.target
+            .split_once(':')
+            .map_or(self.target.as_str(), |(session, _)| session)
+    }
+
+    /// The tmux command that attaches, or with `switch_client` moves the
+    /// current client (nesting a client inside tmux is refused)
+    pub fn command(&self, switch_client: bool) -> Command {
+        let mut cmd = tmux::tmux_command();
+        if switch_client {
+            // switch-client -r would toggle read-only on the user's own client,
+            // which outlives this switch, so read-only only applies to attach
+            cmd.args(["switch-client", "-t", &self.target]);
+        } else {
+            cmd.args(["attach-session", "-t", &self.target]);
+            if self.read_only {
+                cmd.arg("-r");
+            }
+        }
+        cmd
+    }
+}
+
+/// A tmux command run before attaching so the client lands on the selected node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreAttach {
+    /// `select-window -t session:index`
+    SelectWindow(String),
+    /// `select-pane -t session:window.pane`
+    SelectPane(String),
+}
+
+impl PreAttach {
+    pub fn run(&self) -> Result<()> {
+        match self {
+            PreAttach::SelectWindow(target) => tmux::select_window(target),
+            PreAttach::SelectPane(target) => tmux::select_pane(target),
+        }
+    }
 }
 
 /// Actions produced by key handling
 #[derive(Debug)]
 pub enum Action {
     Quit,
-    Attach(String),
+    Attach(AttachRequest),
     Refresh,
     None,
 }
 
+/// Severity of a flash message, used for its color and lifetime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Success,
+    Error,
+}
+
 /// Flash message shown in the status bar
 #[derive(Debug, Clone)]
 pub struct FlashMessage {
     pub text: String,
+    pub level: Level,
     pub created: Instant,
 }
 
 impl FlashMessage {
-    pub fn new(text: impl Into<String>) -> Self {
+    pub fn new(level: Level, text: impl Into<String>) -> Self {
         Self {
             text: text.into(),
+            level,
             created: Instant::now(),
         }
     }
 
-    pub fn is_expired(&self) -> bool {
-        self.created.elapsed().as_secs() >= 3
+    pub fn info(text: impl Into<String>) -> Self {
+        Self::new(Level::Info, text)
     }
+
+    pub fn success(text: impl Into<String>) -> Self {
+        Self::new(Level::Success, text)
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        Self::new(Level::Error, text)
+    }
+
+    pub fn is_expired(&self, lifetime: Duration) -> bool {
+        self.created.elapsed() >= lifetime
+    }
+}
+
+/// Maximum number of queued flash messages; older ones are dropped first
+const MAX_FLASH_QUEUE: usize = 5;
+
+/// Default for `--flash-duration`
+const FLASH_DURATION: Duration = Duration::from_secs(3);
+
+/// Default for `--error-duration`; errors linger so they can actually be read
+const ERROR_DURATION: Duration = Duration::from_secs(6);
+
+/// Default for `--refresh-interval`
+pub const AUTO_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Columns `<` / `>` scroll the selected row by
+const H_SCROLL_STEP: usize = 8;
+
+/// How long a refresh may run before the status bar shows a spinner
+const SPINNER_DELAY: Duration = Duration::from_millis(300);
+
+/// How long a leading `g` waits for its second `g`
+const PENDING_KEY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Pane preview height unless `--preview-lines` says otherwise
+const DEFAULT_PREVIEW_LINES: u16 = 20;
+
+/// Upper bound on preview lines, so `+` can't pull in an enormous scrollback
+const MAX_PREVIEW_LINES: u16 = 200;
+
+/// Rows around the preview text: popup borders and the screen margin
+const PREVIEW_CHROME: u16 = 4;
+
+/// Session notes file in the data directory, a JSON map of name → note
+const NOTES_FILE: &str = "notes.json";
+/// Pinned sessions file in the data directory, a JSON array of names
+const PINNED_FILE: &str = "pinned.json";
+/// Session last attached to from tmxu, a JSON string (or null)
+const LAST_ATTACHED_FILE: &str = "last-attached.json";
+
+/// Session data gathered by one background refresh
+struct Snapshot {
+    sessions: Vec<TmuxSession>,
+    server_running: bool,
+    /// Server start time, fetched only while the status-bar clock is shown
+    server_started: Option<u64>,
 }
 
-const AUTO_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Outcome of a background refresh
+type RefreshResult = Result<Snapshot>;
+
+/// Session name offered when `n` starts a stopped server
+const FIRST_SESSION_NAME: &str = "main";
 
 /// Main application state (Model)
 pub struct App {
     pub sessions: Vec<TmuxSession>,
     pub tree_state: TreeState<String>,
     pub mode: Mode,
-    pub flash: Option<FlashMessage>,
+    /// Pending flash messages; the front one is currently displayed
+    pub flash: VecDeque<FlashMessage>,
     pub banner: Option<Text<'static>>,
+    /// Something visible changed since the last draw
+    pub dirty: bool,
+    /// Last error hit while rendering the tree, so it is only flashed once
+    pub render_error: Option<String>,
+    /// Show pane PIDs and full foreground command lines
+    pub show_processes: bool,
+    /// Show CPU and memory of each pane's foreground process
+    pub show_usage: bool,
+    /// Show tmux's session id (`$0`) after each session name
+    pub show_session_ids: bool,
+    /// Where the status bar goes (`--layout`)
+    pub layout: StatusPosition,
+    /// Node and selection markers in the tree (`--symbols`)
+    pub symbols: TreeSymbols,
+    /// Login shell name; windows still named after it show their active
+    /// command instead (`--command-names`)
+    pub shell_name: Option<String>,
+    /// Show the time and server uptime in the status bar
+    pub show_clock: bool,
+    /// Server start time (Unix seconds), while `show_clock` is on
+    pub server_started: Option<u64>,
+    /// Local time's offset from UTC in seconds, for the clock
+    pub utc_offset: i64,
+    /// Minute the clock last showed, so `tick` redraws when it turns over
+    clock_minute: u64,
+    /// List detached sessions too; off narrows the tree to attached ones
+    pub show_detached: bool,
+    /// Label windows (and address them with the number keys) by tmux's
+    /// `window_index` instead of their position in the list
+    pub tmux_indices: bool,
+    /// Group sessions under synthetic nodes by name prefix
+    pub group_sessions: bool,
+    /// Color session names by a stable hash of the name
+    pub hash_colors: bool,
+    /// Abbreviate window paths fish-style (`~/c/p/tmxu`)
+    pub fish_paths: bool,
+    /// List panes under every window, not just multi-pane ones
+    pub always_show_panes: bool,
+    /// Template for window summaries (`--window-format`)
+    pub window_format: String,
+    /// Ask before `q`/Esc quits
+    pub confirm_quit: bool,
+    /// Ask before `d` kills a session (off with `--no-confirm-kill`)
+    pub confirm_kill: bool,
+    /// Per-session notes keyed by session name, persisted in `NOTES_FILE`
+    pub notes: HashMap<String, String>,
+    /// Sessions kept at the top of the list, persisted in `PINNED_FILE`
+    pub pinned: HashSet<String>,
+    /// Session last attached to from tmxu, persisted in `LAST_ATTACHED_FILE`
+    /// so it stays marked after detaching
+    pub last_attached: Option<String>,
+    /// Running inside tmux, so attaching switches this client instead
+    pub inside_tmux: bool,
+    /// Whether a tmux server answered on the last refresh
+    pub server_running: bool,
+    /// Terminal width at the last draw, to tell whether an error fits the status bar
+    pub screen_width: u16,
+    /// Terminal height at the last draw, which caps the pane preview
+    pub screen_height: u16,
+    /// Visible rows of the tree at the last draw
+    pub tree_height: u16,
+    /// How often sessions are refreshed in the background
+    refresh_interval: Duration,
+    /// How long info and success flash messages stay up
+    flash_duration: Duration,
+    /// How long error flash messages stay up
+    error_duration: Duration,
+    /// Order of windows within each session
+    window_sort: WindowSort,
+    /// Lines the pane preview captures (`--preview-lines`, then `+`/`-`)
+    pub preview_lines: u16,
+    /// Terminal command that the menu's `T` opens attaches in (`--terminal`)
+    terminal: Option<String>,
+    /// Horizontal scroll of the selected row, valid while `h_scroll_path` is selected
+    h_scroll: usize,
+    h_scroll_path: Vec<String>,
     last_refresh: Instant,
+    refresh_tx: Sender<RefreshResult>,
+    refresh_rx: Receiver<RefreshResult>,
+    /// A fetch is running on the worker thread
+    refresh_in_flight: bool,
+    /// When the running fetch was started
+    refresh_started: Instant,
+    /// Animation frame of the busy spinner, advanced by `tick()`
+    spinner_frame: usize,
+    /// Another refresh was requested while one was in flight
+    refresh_queued: bool,
+    /// Set when `g` was pressed and we're waiting for the second `g`
+    pending_g: Option<Instant>,
+    /// First letter of a possible two-letter session label, and when it was typed
+    pending_letter: Option<(char, Instant)>,
+    /// Session the selection was last on, and the one before it (for Tab)
+    current_session: Option<String>,
+    previous_session: Option<String>,
+    /// Tree nodes that were open before `,` `z` collapsed to sessions only,
+    /// restored by pressing it again
+    saved_open: Option<HashSet<Vec<String>>>,
 }
 
 impl App {
-    pub fn new(no_logo: bool) -> Result<Self> {
-        let sessions = tmux::fetch_sessions().unwrap_or_default();
-        let banner = if no_logo {
+    pub fn new(args: &Args) -> Result<Self> {
+        let mut sessions = tmux::fetch_sessions().unwrap_or_default();
+        tmux::fill_branches(&mut sessions);
+        if args.window_sort == WindowSort::Activity {
+            tmux::sort_windows_by_activity(&mut sessions);
+        }
+        let banner = if args.no_logo {
             None
         } else {
             Some(ui::render_banner())
         };
+        // A server with no sessions is rare (tmux exits when the last one dies)
+        let server_running = !sessions.is_empty() || tmux::is_tmux_server_running();
+        let (refresh_tx, refresh_rx) = mpsc::channel();
+        let (notes, notes_error) = match store::load(NOTES_FILE) {
+            Ok(notes) => (notes, None),
+            Err(e) => (HashMap::new(), Some(e)),
+        };
+        let (pinned, pinned_error) = match store::load(PINNED_FILE) {
+            Ok(pinned) => (pinned, None),
+            Err(e) => (HashSet::new(), Some(e)),
+        };
+        // Only a marker, so an unreadable file just means no marker
+        let last_attached = store::load(LAST_ATTACHED_FILE).unwrap_or_default();
         let mut app = Self {
             sessions,
             tree_state: TreeState::default(),
             mode: Mode::Normal,
-            flash: None,
+            flash: VecDeque::new(),
             banner,
+            dirty: true,
+            render_error: None,
+            show_processes: false,
+            show_usage: false,
+            show_session_ids: false,
+            layout: args.layout,
+            symbols: args.symbols.clone(),
+            shell_name: if args.command_names {
+                tmux::default_shell()
+            } else {
+                None
+            },
+            show_clock: args.clock,
+            server_started: None,
+            utc_offset: if args.clock {
+                ui::local_utc_offset()
+            } else {
+                0
+            },
+            clock_minute: 0,
+            tmux_indices: false,
+            show_detached: true,
+            group_sessions: false,
+            hash_colors: args.hash_colors,
+            fish_paths: args.fish_paths,
+            always_show_panes: args.all_panes,
+            window_format: args
+                .window_format
+                .clone()
+                .unwrap_or_else(|| tmux::DEFAULT_WINDOW_FORMAT.to_string()),
+            confirm_quit: args.confirm_quit,
+            confirm_kill: !args.no_confirm_kill,
+            notes,
+            pinned,
+            last_attached,
+            inside_tmux: tmux::inside_tmux(),
+            server_running,
+            screen_width: 0,
+            screen_height: 0,
+            tree_height: 0,
+            refresh_interval: args.refresh_interval.unwrap_or(AUTO_REFRESH_INTERVAL),
+            flash_duration: args.flash_duration.unwrap_or(FLASH_DURATION),
+            error_duration: args.error_duration.unwrap_or(ERROR_DURATION),
+            window_sort: args.window_sort,
+            terminal: args.terminal.clone(),
+            preview_lines: args
+                .preview_lines
+                .unwrap_or(DEFAULT_PREVIEW_LINES)
+                .min(MAX_PREVIEW_LINES),
+            h_scroll: 0,
+            h_scroll_path: Vec::new(),
             last_refresh: Instant::now(),
+            refresh_tx,
+            refresh_rx,
+            refresh_in_flight: false,
+            refresh_started: Instant::now(),
+            spinner_frame: 0,
+            refresh_queued: false,
+            pending_g: None,
+            pending_letter: None,
+            current_session: None,
+            previous_session: None,
+            saved_open: None,
         };
+        pinned_first(&mut app.sessions, &app.pinned);
         if let Some(session) = app.sessions.first() {
             // Open the first session and select its first window
-            app.tree_state.open(vec![session.name.clone()]);
+            let mut path = vec![session.name.clone()];
             if let Some(window) = session.windows.first() {
-                app.tree_state
-                    .select(vec![session.name.clone(), format!("{}", window.index)]);
-            } else {
-                app.tree_state.select_first();
+                path.push(format!("{}", window.index));
+            }
+            app.select_path(path);
+        }
+        if args.expand_all {
+            app.set_sessions_open(true);
+        }
+        app.current_session = app.selection().into_iter().next();
+        if let Some(version @ (major, minor)) = tmux::detected_version() {
+            if version < tmux::MIN_VERSION {
+                let (min_major, min_minor) = tmux::MIN_VERSION;
+                app.push_flash(FlashMessage::error(format!(
+                    "tmux {major}.{minor} is older than {min_major}.{min_minor}: \
+                     idle times, titles and pane state are hidden"
+                )));
             }
         }
+        if let Some(e) = notes_error {
+            app.push_flash(FlashMessage::error(format!("Notes not loaded: {e}")));
+        }
+        if let Some(e) = pinned_error {
+            app.push_flash(FlashMessage::error(format!("Pins not loaded: {e}")));
+        }
         Ok(app)
     }
 
-    /// Refresh session data from tmux
+    /// Current tree selection with any group node stripped, so index 0 is
+    /// always the session name (then window index, then pane index)
+    pub fn selection(&self) -> Vec<String> {
+        let selected = self.tree_state.selected();
+        match selected.first() {
+            Some(id) if ui::is_group_id(id) => selected[1..].to_vec(),
+            _ => selected.to_vec(),
+        }
+    }
+
+    /// Open the ancestors of `path` (session first) and select it,
+    /// adding the session's group node when grouping is on
+    fn select_path(&mut self, path: Vec<String>) {
+        let mut full = Vec::with_capacity(path.len() + 1);
+        if self.group_sessions {
+            if let Some(group) = path.first().and_then(|name| tmux::session_group(name)) {
+                full.push(ui::group_id(group));
+                self.tree_state.open(full.clone());
+            }
+        }
+        for id in path {
+            full.push(id);
+            self.tree_state.open(full.clone());
+        }
+        self.tree_state.select(full);
+    }
+
+    /// Move the selection off anything the latest refresh removed: onto the
+    /// session's active window or pane, or the first session if it is gone
+    fn reconcile_selection(&mut self) {
+        let selected = self.selection();
+        if selected.is_empty() {
+            return;
+        }
+        let resolved =
+            tmux::resolve_path(&self.visible_sessions(), &selected, self.always_show_panes);
+        match resolved {
+            Some(path) if path == selected => {}
+            Some(path) => self.select_path(path),
+            None => self.jump_to_session(0),
+        }
+    }
+
+    /// Tree id path of session `name`, through its group node when grouping is on
+    fn session_tree_path(&self, name: &str) -> Vec<String> {
+        let mut path = Vec::with_capacity(2);
+        if self.group_sessions {
+            if let Some(group) = tmux::session_group(name) {
+                path.push(ui::group_id(group));
+            }
+        }
+        path.push(name.to_string());
+        path
+    }
+
+    /// Expand or collapse every session node. Collapsing moves a selection
+    /// inside a session up to the session so it stays visible.
+    fn set_sessions_open(&mut self, open: bool) {
+        let paths: Vec<Vec<String>> = self
+            .sessions
+            .iter()
+            .map(|s| self.session_tree_path(&s.name))
+            .collect();
+        for path in paths {
+            if open {
+                if path.len() > 1 {
+                    self.tree_state.open(path[..1].to_vec());
+                }
+                self.tree_state.open(path);
+            } else {
+                self.tree_state.close(&path);
+            }
+        }
+
+        if !open {
+            if let Some(session) = self.selection().first() {
+                let path = self.session_tree_path(session);
+                self.tree_state.select(path);
+            }
+        }
+    }
+
+    /// Collapse to session rows only, remembering what was open, or put
+    /// back the expansion saved by the previous `,` `z`
+    fn toggle_sessions_only(&mut self) {
+        match self.saved_open.take() {
+            Some(saved) => {
+                let opened: Vec<Vec<String>> = self.tree_state.opened().iter().cloned().collect();
+                for path in opened {
+                    self.tree_state.close(&path);
+                }
+                for path in saved {
+                    self.tree_state.open(path);
+                }
+                self.push_flash(FlashMessage::info("Expansion restored"));
+            }
+            None => {
+                self.saved_open = Some(self.tree_state.opened().clone());
+                self.set_sessions_open(false);
+                self.push_flash(FlashMessage::info("Sessions only (z restores)"));
+            }
+        }
+    }
+
+    /// Horizontal scroll offset for the selected row
+    pub fn selected_scroll(&self) -> usize {
+        if self.tree_state.selected() == self.h_scroll_path.as_slice() {
+            self.h_scroll
+        } else {
+            0
+        }
+    }
+
+    /// Scroll the selected row's text; moving the selection resets the offset
+    fn scroll_selected(&mut self, delta: isize) {
+        let offset = self.selected_scroll();
+        self.h_scroll = offset.saturating_add_signed(delta);
+        self.h_scroll_path = self.tree_state.selected().to_vec();
+    }
+
+    /// Toggle grouping by name prefix, keeping the current selection
+    fn toggle_grouping(&mut self) {
+        let path = self.selection();
+        self.group_sessions = !self.group_sessions;
+        if self.group_sessions {
+            let groups: Vec<String> = self
+                .sessions
+                .iter()
+                .filter_map(|s| tmux::session_group(&s.name))
+                .map(ui::group_id)
+                .collect();
+            for group in groups {
+                self.tree_state.open(vec![group]);
+            }
+        }
+        if !path.is_empty() {
+            self.select_path(path);
+        }
+    }
+
+    /// Toggle listing single panes; a pane leaf that disappears hands the
+    /// selection to its window
+    fn toggle_always_show_panes(&mut self) {
+        self.always_show_panes = !self.always_show_panes;
+        let mut path = self.selection();
+        if !self.always_show_panes && path.len() == 3 {
+            let single = self
+                .sessions
+                .iter()
+                .find(|s| s.name == path[0])
+                .and_then(|s| s.windows.iter().find(|w| w.index.to_string() == path[1]))
+                .is_some_and(|w| w.panes.len() == 1);
+            if single {
+                path.pop();
+                self.select_path(path);
+            }
+        }
+    }
+
+    /// Refresh session data from tmux on a worker thread.
+    /// Results are picked up by `tick()` so the event loop never blocks on tmux.
     pub fn refresh(&mut self) {
         self.last_refresh = Instant::now();
-        match tmux::fetch_sessions() {
-            Ok(sessions) => {
-                self.sessions = sessions;
+        if self.refresh_in_flight {
+            // The running fetch may predate whatever triggered this refresh
+            self.refresh_queued = true;
+            return;
+        }
+        self.refresh_in_flight = true;
+        self.refresh_started = Instant::now();
+        let tx = self.refresh_tx.clone();
+        let with_processes = self.show_processes;
+        let with_usage = self.show_usage;
+        let with_clock = self.show_clock;
+        let window_sort = self.window_sort;
+        thread::spawn(move || {
+            let result = tmux::fetch_sessions().map(|mut sessions| {
+                tmux::fill_branches(&mut sessions);
+                if window_sort == WindowSort::Activity {
+                    tmux::sort_windows_by_activity(&mut sessions);
+                }
+                if with_processes {
+                    tmux::fill_command_lines(&mut sessions);
+                }
+                if with_usage {
+                    tmux::fill_usage(&mut sessions);
+                }
+                let server_running = !sessions.is_empty() || tmux::is_tmux_server_running();
+                let server_started = (with_clock && server_running)
+                    .then(|| tmux::server_info().ok().map(|info| info.start_time))
+                    .flatten();
+                Snapshot {
+                    sessions,
+                    server_running,
+                    server_started,
+                }
+            });
+            // The receiver only disappears when the app is shutting down
+            let _ = tx.send(result);
+        });
+    }
+
+    /// Apply any session data delivered by the refresh worker
+    fn poll_refresh(&mut self) {
+        while let Ok(result) = self.refresh_rx.try_recv() {
+            if self.spinner().is_some() {
+                // Clear the spinner even if the data didn't change
+                self.dirty = true;
             }
-            Err(e) => {
-                self.flash = Some(FlashMessage::new(format!("Refresh failed: {e}")));
+            self.refresh_in_flight = false;
+            match result {
+                Ok(mut snapshot) => {
+                    pinned_first(&mut snapshot.sessions, &self.pinned);
+                    if snapshot.sessions != self.sessions
+                        || snapshot.server_running != self.server_running
+                        || snapshot.server_started != self.server_started
+                    {
+                        self.sessions = snapshot.sessions;
+                        self.server_running = snapshot.server_running;
+                        self.server_started = snapshot.server_started;
+                        self.dirty = true;
+                        self.reconcile_selection();
+                    }
+                }
+                Err(e) => {
+                    self.push_flash(FlashMessage::error(format!("Refresh failed: {e}")));
+                }
+            }
+        }
+
+        if !self.refresh_in_flight && self.refresh_queued {
+            self.refresh_queued = false;
+            self.refresh();
+        }
+    }
+
+    /// Busy spinner frame, once a refresh has been running long enough to notice
+    pub fn spinner(&self) -> Option<usize> {
+        (self.refresh_in_flight && self.refresh_started.elapsed() >= SPINNER_DELAY)
+            .then_some(self.spinner_frame)
+    }
+
+    /// Queue a flash message, dropping the oldest if the queue is full
+    pub fn push_flash(&mut self, flash: FlashMessage) {
+        if self.flash.len() >= MAX_FLASH_QUEUE {
+            self.flash.pop_front();
+            if let Some(next) = self.flash.front_mut() {
+                next.created = Instant::now();
             }
         }
+        self.flash.push_back(flash);
+        self.dirty = true;
+    }
+
+    /// How long a flash message of `level` stays on screen
+    fn flash_lifetime(&self, level: Level) -> Duration {
+        match level {
+            Level::Info | Level::Success => self.flash_duration,
+            Level::Error => self.error_duration,
+        }
+    }
+
+    /// Report an error: a flash if it fits on one status line, else the error popup
+    pub fn show_error(&mut self, message: String) {
+        // The status bar indents its message by two columns
+        let fits = usize::from(self.screen_width).saturating_sub(2);
+        if message.contains('\n') || message.chars().count() > fits {
+            self.mode = Mode::Error { message, scroll: 0 };
+            self.dirty = true;
+        } else {
+            self.push_flash(FlashMessage::error(message));
+        }
+    }
+
+    /// Report a failed tmux operation. Under `--dry-run` the skipped command is
+    /// shown as info rather than an error.
+    fn report_error(&mut self, e: Report) {
+        match e.downcast_ref::<tmux::DryRun>() {
+            Some(dry_run) => self.push_flash(FlashMessage::info(dry_run.to_string())),
+            None => self.show_error(format!("Error: {e}")),
+        }
     }
 
-    /// Periodic housekeeping: expire flash messages, auto-refresh sessions
+    /// Periodic housekeeping: apply background refreshes, expire flash messages
+    /// and pending keys, auto-refresh sessions
     pub fn tick(&mut self) {
-        if let Some(ref flash) = self.flash {
-            if flash.is_expired() {
-                self.flash = None;
+        self.poll_refresh();
+
+        if self.spinner().is_some() {
+            self.spinner_frame = self.spinner_frame.wrapping_add(1);
+            self.dirty = true;
+        }
+
+        if self
+            .pending_g
+            .is_some_and(|t| t.elapsed() >= PENDING_KEY_TIMEOUT)
+        {
+            self.pending_g = None;
+        }
+        // Picking a move destination waits for its second letter however long
+        let picking = matches!(self.mode, Mode::MoveWindow { .. });
+        if !picking
+            && self
+                .pending_letter
+                .is_some_and(|(_, t)| t.elapsed() >= PENDING_KEY_TIMEOUT)
+        {
+            self.pending_letter = None;
+        }
+
+        if self
+            .flash
+            .front()
+            .is_some_and(|flash| flash.is_expired(self.flash_lifetime(flash.level)))
+        {
+            self.flash.pop_front();
+            self.dirty = true;
+            // The next message's lifetime starts when it becomes visible
+            if let Some(next) = self.flash.front_mut() {
+                next.created = Instant::now();
             }
         }
 
-        if self.last_refresh.elapsed() >= AUTO_REFRESH_INTERVAL {
+        if self.show_clock {
+            let minute = ui::unix_now() / 60;
+            if minute != self.clock_minute {
+                self.clock_minute = minute;
+                self.dirty = true;
+            }
+        }
+
+        if self.last_refresh.elapsed() >= self.refresh_interval {
             self.refresh();
         }
     }
 
     /// Handle a key event and return an Action
     pub fn handle_key_event(&mut self, key: KeyEvent) -> Action {
-        match &self.mode {
+        self.dirty = true;
+        let action = match &self.mode {
             Mode::Normal => self.handle_normal_key(key),
             Mode::CreateSession { .. } => self.handle_create_session_key(key),
             Mode::RenameSession { .. } => self.handle_rename_session_key(key),
             Mode::ConfirmKill { .. } => self.handle_confirm_kill_key(key),
+            Mode::ConfirmQuit => self.handle_confirm_quit_key(key),
+            Mode::ConfirmKillServer { .. } => self.handle_confirm_kill_server_key(key),
+            Mode::MoveWindow { .. } => self.handle_move_window_key(key),
+            Mode::SendKeys { .. } => self.handle_send_keys_key(key),
+            Mode::EditNote { .. } => self.handle_edit_note_key(key),
+            Mode::Error { .. } => self.handle_error_key(key),
+            Mode::Environment { .. } => self.handle_environment_key(key),
+            Mode::Search { .. } => self.handle_search_key(key),
+            Mode::Preview { .. } => self.handle_preview_key(key),
+            Mode::Menu { .. } => self.handle_menu_key(key),
+        };
+        self.track_session();
+        action
+    }
+
+    /// Point the last-attached marker at `session`, e.g. after a rename
+    fn remember_attach(&mut self, session: &str) {
+        self.last_attached = Some(session.to_string());
+        save_last_attached(session);
+    }
+
+    /// Remember the last session left, so Tab can return to it
+    fn track_session(&mut self) {
+        let session = self.selection().into_iter().next();
+        if session.is_some() && session != self.current_session {
+            self.previous_session = std::mem::replace(&mut self.current_session, session);
+        }
+    }
+
+    /// Jump back to the previously selected session, like tmux's `last-window`
+    fn jump_to_previous_session(&mut self) {
+        let Some(name) = self.previous_session.clone() else {
+            return;
+        };
+        let Some(session) = self.sessions.iter().find(|s| s.name == name) else {
+            self.push_flash(FlashMessage::info(format!("Session '{name}' is gone")));
+            return;
+        };
+        let mut path = vec![session.name.clone()];
+        if let Some(window) = session.windows.iter().find(|w| w.active) {
+            path.push(format!("{}", window.index));
+        }
+        self.select_path(path);
+    }
+
+    /// Select the next (or previous) session's own row, however the
+    /// sessions in between are expanded; stops at either end
+    fn step_session(&mut self, forward: bool) {
+        let order = self.session_order();
+        let current = self.selection().into_iter().next();
+        let position = current.and_then(|name| order.iter().position(|n| *n == name));
+        let next = match position {
+            Some(i) if forward => (i + 1).min(order.len().saturating_sub(1)),
+            Some(i) => i.saturating_sub(1),
+            None => 0,
+        };
+        if let Some(name) = order.get(next).cloned() {
+            self.select_path(vec![name]);
+        }
+    }
+
+    /// Visible session names in tree order: as listed, except that grouping
+    /// pulls each group's members up to where its first member is
+    fn session_order(&self) -> Vec<String> {
+        let mut groups: Vec<(Option<&str>, Vec<String>)> = Vec::new();
+        for session in self.visible_sessions() {
+            let group = tmux::session_group(&session.name).filter(|_| self.group_sessions);
+            match group.and_then(|g| groups.iter_mut().find(|(n, _)| *n == Some(g))) {
+                Some((_, members)) => members.push(session.name.clone()),
+                None => groups.push((group, vec![session.name.clone()])),
+            }
         }
+        groups
+            .into_iter()
+            .flat_map(|(_, members)| members)
+            .collect()
     }
 
     fn handle_normal_key(&mut self, key: KeyEvent) -> Action {
+        // Any key consumes a pending `g`; only a second `g` acts on it
+        let pending_g = self.pending_g.take().is_some();
+
+        // A letter right after another completes a two-letter label (AA, AB, …),
+        // taking precedence over the second letter's own binding. An uppercase
+        // first letter attaches, in whatever case the second is typed.
+        if let (Some((first, _)), KeyCode::Char(c)) = (self.pending_letter.take(), key.code) {
+            let attach = first.is_ascii_uppercase();
+            if c.is_ascii_lowercase() || (attach && c.is_ascii_uppercase()) {
+                let idx = ui::session_index(&format!("{first}{c}"));
+                if let Some(idx) = idx.filter(|&i| i < self.visible_sessions().len()) {
+                    self.jump_to_session(idx);
+                    return if attach {
+                        self.action_attach(false)
+                    } else {
+                        Action::None
+                    };
+                }
+            }
+        }
+
         match key.code {
+            KeyCode::Char('q') | KeyCode::Esc if self.confirm_quit => {
+                self.mode = Mode::ConfirmQuit;
+                Action::None
+            }
             KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
 
+            // Shift+arrows drag the selected window along its session
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.action_swap_window(true)
+            }
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.action_swap_window(false)
+            }
+
             // Navigation
             KeyCode::Char('j') | KeyCode::Down => {
                 self.tree_state.key_down();
                 Action::None
             }
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.mode = Mode::ConfirmKillServer {
+                    input: String::new(),
+                };
+                Action::None
+            }
             KeyCode::Char('k') | KeyCode::Up => {
                 self.tree_state.key_up();
                 Action::None
             }
             KeyCode::Char('g') => {
-                self.tree_state.select_first();
+                if pending_g {
+                    self.tree_state.select_first();
+                } else {
+                    self.pending_g = Some(Instant::now());
+                }
                 Action::None
             }
-            KeyCode::Char('G') => {
+            KeyCode::Char('G') | KeyCode::End => {
                 self.tree_state.select_last();
                 Action::None
             }
+            KeyCode::Home => {
+                self.tree_state.select_first();
+                Action::None
+            }
+            KeyCode::PageDown => {
+                let page = self.page_size();
+                self.tree_state
+                    .select_relative(|current| current.map_or(0, |i| i.saturating_add(page)));
+                Action::None
+            }
+            KeyCode::PageUp => {
+                let page = self.page_size();
+                self.tree_state
+                    .select_relative(|current| current.map_or(0, |i| i.saturating_sub(page)));
+                Action::None
+            }
+            KeyCode::Tab | KeyCode::Char('`') => {
+                self.jump_to_previous_session();
+                Action::None
+            }
+            KeyCode::Char('}') => {
+                self.step_session(true);
+                Action::None
+            }
+            KeyCode::Char('{') => {
+                self.step_session(false);
+                Action::None
+            }
+
+            // Scroll long text in the selected row
+            KeyCode::Char('<') => {
+                self.scroll_selected(-(H_SCROLL_STEP as isize));
+                Action::None
+            }
+            KeyCode::Char('>') => {
+                self.scroll_selected(H_SCROLL_STEP as isize);
+                Action::None
+            }
 
             // Expand / Collapse
             KeyCode::Char(' ') | KeyCode::Char('l') | KeyCode::Right => {
@@ -155,35 +1149,97 @@ impl App {
                 self.tree_state.key_left();
                 Action::None
             }
+            KeyCode::Char('+') => {
+                self.set_sessions_open(true);
+                Action::None
+            }
+            KeyCode::Char('-') => {
+                self.set_sessions_open(false);
+                Action::None
+            }
 
             // Attach
-            KeyCode::Enter => self.action_attach(),
+            KeyCode::Enter => self.action_attach(false),
 
             // Session management
             KeyCode::Char('n') => {
+                // With no server, creating a session is also how it gets started
+                let input = if self.server_running {
+                    String::new()
+                } else {
+                    FIRST_SESSION_NAME.to_string()
+                };
                 self.mode = Mode::CreateSession {
-                    input: String::new(),
+                    input,
+                    start_dir: None,
+                    error: None,
                 };
                 Action::None
             }
             KeyCode::Char('d') => self.action_start_kill(),
-            KeyCode::Char('r') => self.action_start_rename(),
+            KeyCode::Char('r') => self.action_start_rename(false),
+            KeyCode::F(2) => self.action_start_rename(true),
+            KeyCode::Char('*') => self.action_toggle_pin(),
+            KeyCode::Char('!') => self.action_start_send_keys(),
             KeyCode::Char('R') => Action::Refresh,
 
-            // Shift+letter: attach to session immediately
+            // Shift+letter: attach to session immediately, unless it starts a
+            // two-letter label; then select it and wait (Enter attaches it alone)
             KeyCode::Char(c @ 'A'..='Z') => {
-                self.jump_to_session(c);
-                self.action_attach()
+                self.jump_to_session((c as u8 - b'A') as usize);
+                if self.starts_two_letter_label(c) {
+                    self.pending_letter = Some((c, Instant::now()));
+                    return Action::None;
+                }
+                self.action_attach(false)
             }
 
-            // Lowercase letter: navigate to session
+            // Lowercase letter: navigate to session, or start a two-letter label
             KeyCode::Char(c @ 'a'..='z') => {
-                self.jump_to_session(c.to_ascii_uppercase());
+                self.jump_to_session((c as u8 - b'a') as usize);
+                if self.starts_two_letter_label(c) {
+                    self.pending_letter = Some((c, Instant::now()));
+                }
+                Action::None
+            }
+
+            KeyCode::Char('#') => {
+                self.tmux_indices = !self.tmux_indices;
+                Action::None
+            }
+            KeyCode::Char('$') => {
+                self.show_session_ids = !self.show_session_ids;
+                Action::None
+            }
+
+            KeyCode::Char(',') => {
+                self.mode = Mode::Menu { selected: 0 };
+                Action::None
+            }
+
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search {
+                    query: String::new(),
+                    selected: 0,
+                };
+                Action::None
+            }
+            KeyCode::Char('.') => {
+                self.jump_to_active_window();
                 Action::None
             }
 
-            // Jump to window by number 1-9
-            KeyCode::Char(c @ '1'..='9') => {
+            // Alt+number: attach to that window immediately
+            KeyCode::Char(c @ '0'..='9') if key.modifiers.contains(KeyModifiers::ALT) => {
+                if self.jump_to_window(c) {
+                    self.action_attach(false)
+                } else {
+                    Action::None
+                }
+            }
+
+            // Jump to window by its label number
+            KeyCode::Char(c @ '0'..='9') => {
                 self.jump_to_window(c);
                 Action::None
             }
@@ -192,47 +1248,117 @@ impl App {
         }
     }
 
-    /// Jump to session by label letter (A=0, B=1, ...)
-    fn jump_to_session(&mut self, letter: char) {
-        let idx = (letter as u8 - b'A') as usize;
-        if let Some(session) = self.sessions.get(idx) {
-            self.tree_state.open(vec![session.name.clone()]);
+    /// Rows PageUp/PageDown move by: the visible tree, at least one
+    fn page_size(&self) -> usize {
+        usize::from(self.tree_height).max(1)
+    }
+
+    /// Whether `c` begins a two-letter label of some visible session
+    fn starts_two_letter_label(&self, c: char) -> bool {
+        let count = self.visible_sessions().len();
+        ui::session_index(&format!("{c}a")).is_some_and(|i| i < count)
+    }
+
+    /// Jump to the session at `idx` (label A=0, B=1, …, AA=26)
+    fn jump_to_session(&mut self, idx: usize) {
+        let path = self.visible_sessions().get(idx).map(|session| {
             // Select the first window in that session
+            let mut path = vec![session.name.clone()];
             if let Some(window) = session.windows.first() {
-                self.tree_state
-                    .select(vec![session.name.clone(), format!("{}", window.index)]);
-            } else {
-                self.tree_state.select(vec![session.name.clone()]);
+                path.push(format!("{}", window.index));
+            }
+            path
+        });
+        if let Some(path) = path {
+            self.select_path(path);
+        }
+    }
+
+    /// First letter of a two-letter session label still being typed
+    pub fn pending_label(&self) -> Option<char> {
+        self.pending_letter.map(|(first, _)| first)
+    }
+
+    /// Sessions shown in the tree, which labels A, B, … index into
+    pub fn visible_sessions(&self) -> Vec<&TmuxSession> {
+        self.sessions
+            .iter()
+            .filter(|s| self.show_detached || s.attached)
+            .collect()
+    }
+
+    /// Hide or show detached sessions, moving the selection off a hidden one
+    fn toggle_show_detached(&mut self) {
+        self.show_detached = !self.show_detached;
+        self.push_flash(FlashMessage::info(if self.show_detached {
+            "Showing all sessions"
+        } else {
+            "Showing attached sessions only (F shows all)"
+        }));
+
+        let selected = self.selection().into_iter().next();
+        let visible = self.visible_sessions();
+        if !visible.iter().any(|s| Some(&s.name) == selected.as_ref()) {
+            let first = visible.first().map(|s| s.name.clone());
+            if let Some(name) = first {
+                self.select_path(vec![name]);
             }
         }
     }
 
-    /// Jump to window N (1-based) within the currently selected session
-    fn jump_to_window(&mut self, digit: char) {
-        let win_display_idx = (digit as u8 - b'0') as usize; // 1-based display index
+    /// Select the window tmux considers active in the selected session
+    fn jump_to_active_window(&mut self) {
+        let Some(session_name) = self.selection().into_iter().next() else {
+            return;
+        };
+        let active = self
+            .sessions
+            .iter()
+            .find(|s| s.name == session_name)
+            .and_then(|s| s.windows.iter().find(|w| w.active));
+        if let Some(window) = active {
+            let path = vec![session_name, window.index.to_string()];
+            self.select_path(path);
+        }
+    }
+
+    /// Jump to the window labeled `digit` within the currently selected session
+    /// Returns whether there was such a window.
+    fn jump_to_window(&mut self, digit: char) -> bool {
+        let number = (digit as u8 - b'0') as usize;
         let session_name = {
-            let selected = self.tree_state.selected();
+            let selected = self.selection();
             if selected.is_empty() {
-                return;
+                return false;
             }
             selected[0].clone()
         };
 
         let session = match self.sessions.iter().find(|s| s.name == session_name) {
             Some(s) => s,
-            None => return,
+            None => return false,
         };
 
-        // win_display_idx is 1-based positional (1st window, 2nd window, ...)
-        if let Some(window) = session.windows.get(win_display_idx - 1) {
-            self.tree_state.open(vec![session_name.clone()]);
-            self.tree_state
-                .select(vec![session_name, format!("{}", window.index)]);
+        let window = ui::labeled_windows(&session.windows, self.tmux_indices)
+            .find(|(label, _)| *label == number)
+            .map(|(_, window)| window);
+        match window {
+            Some(window) => {
+                let path = vec![session_name, format!("{}", window.index)];
+                self.select_path(path);
+                true
+            }
+            None => false,
         }
     }
 
     fn handle_create_session_key(&mut self, key: KeyEvent) -> Action {
-        let Mode::CreateSession { ref mut input } = self.mode else {
+        let Mode::CreateSession {
+            ref mut input,
+            ref start_dir,
+            ref mut error,
+        } = self.mode
+        else {
             return Action::None;
         };
 
@@ -246,33 +1372,72 @@ impl App {
                     self.mode = Mode::Normal;
                     return Action::None;
                 }
-                match tmux::create_session(&name) {
-                    Ok(()) => {
-                        self.flash = Some(FlashMessage::new(format!("Created session '{name}'")));
+                if let Err(reason) = tmux::validate_session_name(&name) {
+                    *error = Some(reason);
+                    return Action::None;
+                }
+                let starting_server = !self.server_running;
+                // Alt+Enter (or Shift+Enter where the terminal reports it)
+                // drops straight into the session, creating it only if needed
+                if key
+                    .modifiers
+                    .intersects(KeyModifiers::ALT | KeyModifiers::SHIFT)
+                {
+                    let start_dir = start_dir.clone();
+                    self.mode = Mode::Normal;
+                    return self.ensure_and_attach(name, start_dir.as_deref());
+                }
+                match tmux::create_session(&name, start_dir.as_deref()) {
+                    Ok(()) => {
+                        self.server_running = true;
                         self.mode = Mode::Normal;
+                        self.push_flash(FlashMessage::success(if starting_server {
+                            format!("Started tmux server with session '{name}'")
+                        } else {
+                            format!("Created session '{name}'")
+                        }));
                         return Action::Refresh;
                     }
                     Err(e) => {
-                        self.flash = Some(FlashMessage::new(format!("Error: {e}")));
                         self.mode = Mode::Normal;
+                        self.report_error(e);
                     }
                 }
             }
             KeyCode::Backspace => {
                 input.pop();
+                *error = None;
             }
             KeyCode::Char(c) => {
                 input.push(c);
+                *error = None;
             }
             _ => {}
         }
         Action::None
     }
 
+    /// Attach to session `name`, creating it first if it doesn't exist
+    fn ensure_and_attach(&mut self, name: String, start_dir: Option<&str>) -> Action {
+        match tmux::ensure_session(&name, start_dir) {
+            Ok(_) => Action::Attach(AttachRequest {
+                target: name,
+                read_only: false,
+                steps: Vec::new(),
+            }),
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
     fn handle_rename_session_key(&mut self, key: KeyEvent) -> Action {
         let Mode::RenameSession {
             ref target,
             ref mut input,
+            ref mut error,
+            ..
         } = self.mode
         else {
             return Action::None;
@@ -289,25 +1454,50 @@ impl App {
                     self.mode = Mode::Normal;
                     return Action::None;
                 }
+                if let Err(reason) = tmux::validate_session_name(&new_name) {
+                    *error = Some(reason);
+                    return Action::None;
+                }
                 match tmux::rename_session(&old_name, &new_name) {
                     Ok(()) => {
-                        self.flash = Some(FlashMessage::new(format!(
+                        // Notes, pins and the last-attached marker are keyed
+                        // by name, so carry them over
+                        if let Some(note) = self.notes.remove(&old_name) {
+                            self.notes.insert(new_name.clone(), note);
+                            self.save_notes();
+                        }
+                        if self.pinned.remove(&old_name) {
+                            self.pinned.insert(new_name.clone());
+                            self.save_pinned();
+                        }
+                        if self.last_attached.as_deref() == Some(old_name.as_str()) {
+                            self.remember_attach(&new_name);
+                        }
+                        self.push_flash(FlashMessage::success(format!(
                             "Renamed '{old_name}' → '{new_name}'"
                         )));
                         self.mode = Mode::Normal;
                         return Action::Refresh;
                     }
                     Err(e) => {
-                        self.flash = Some(FlashMessage::new(format!("Error: {e}")));
                         self.mode = Mode::Normal;
+                        self.report_error(e);
                     }
                 }
             }
+            // Keep a `proj/` or `proj:` prefix and retype only the rest
+            KeyCode::Tab => {
+                let keep = input.rfind([':', '/']).map_or(0, |i| i + 1);
+                input.truncate(keep);
+                *error = None;
+            }
             KeyCode::Backspace => {
                 input.pop();
+                *error = None;
             }
             KeyCode::Char(c) => {
                 input.push(c);
+                *error = None;
             }
             _ => {}
         }
@@ -315,35 +1505,411 @@ impl App {
     }
 
     fn handle_confirm_kill_key(&mut self, key: KeyEvent) -> Action {
-        let Mode::ConfirmKill { ref target } = self.mode else {
+        let Mode::ConfirmKill { ref target, .. } = self.mode else {
             return Action::None;
         };
 
         match key.code {
             KeyCode::Char('y') | KeyCode::Char('Y') => {
                 let target = target.clone();
-                match tmux::kill_session(&target) {
+                self.mode = Mode::Normal;
+                self.kill_session(&target)
+            }
+            _ => {
+                self.mode = Mode::Normal;
+                Action::None
+            }
+        }
+    }
+
+    fn kill_session(&mut self, target: &str) -> Action {
+        match tmux::kill_session(target) {
+            Ok(()) => {
+                self.push_flash(FlashMessage::success(format!("Killed session '{target}'")));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    fn handle_confirm_quit_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => Action::Quit,
+            _ => {
+                self.mode = Mode::Normal;
+                Action::None
+            }
+        }
+    }
+
+    fn handle_confirm_kill_server_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::ConfirmKillServer { ref mut input } = self.mode else {
+            return Action::None;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => {
+                let confirmed = input.trim() == "yes";
+                self.mode = Mode::Normal;
+                if !confirmed {
+                    self.push_flash(FlashMessage::info("Kill server cancelled"));
+                    return Action::None;
+                }
+                match tmux::kill_server() {
                     Ok(()) => {
-                        self.flash = Some(FlashMessage::new(format!("Killed session '{target}'")));
-                        self.mode = Mode::Normal;
+                        self.sessions.clear();
+                        self.server_running = false;
+                        self.push_flash(FlashMessage::success("Killed tmux server"));
                         return Action::Refresh;
                     }
                     Err(e) => {
-                        self.flash = Some(FlashMessage::new(format!("Error: {e}")));
-                        self.mode = Mode::Normal;
+                        self.report_error(e);
+                    }
+                }
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn handle_send_keys_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::SendKeys {
+            ref target,
+            ref mut input,
+        } = self.mode
+        else {
+            return Action::None;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => {
+                let target = target.clone();
+                let keys = std::mem::take(input);
+                self.mode = Mode::Normal;
+                if keys.is_empty() {
+                    return Action::None;
+                }
+                match tmux::send_keys(&target, &keys) {
+                    Ok(()) => {
+                        self.push_flash(FlashMessage::success(format!(
+                            "Sent '{keys}' to '{target}'"
+                        )));
+                        return Action::Refresh;
+                    }
+                    Err(e) => {
+                        self.report_error(e);
                     }
                 }
             }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn handle_error_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::Error { ref mut scroll, .. } = self.mode else {
+            return Action::None;
+        };
+
+        // j/k scroll a long message; anything else dismisses it
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => *scroll = scroll.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => *scroll = scroll.saturating_sub(1),
+            _ => self.mode = Mode::Normal,
+        }
+        Action::None
+    }
+
+    fn handle_environment_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::Environment { ref mut scroll, .. } = self.mode else {
+            return Action::None;
+        };
+
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => *scroll = scroll.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => *scroll = scroll.saturating_sub(1),
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Normal,
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn handle_preview_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::Preview { ref target, .. } = self.mode else {
+            return Action::None;
+        };
+        let target = target.clone();
+
+        let lines = match key.code {
+            KeyCode::Char('+') | KeyCode::Char('=') => self.preview_lines.saturating_add(5),
+            KeyCode::Char('-') => self.preview_lines.saturating_sub(5),
             _ => {
                 self.mode = Mode::Normal;
+                return Action::None;
+            }
+        };
+        // More lines than the popup can show would only be cut off
+        let fits = self.screen_height.saturating_sub(PREVIEW_CHROME).max(1);
+        self.preview_lines = lines.clamp(1, MAX_PREVIEW_LINES.min(fits));
+        self.open_preview(target);
+        Action::None
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::Search {
+            ref mut query,
+            ref mut selected,
+        } = self.mode
+        else {
+            return Action::None;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Normal,
+            KeyCode::Up | KeyCode::Char('p') if key.code == KeyCode::Up || ctrl => {
+                *selected = selected.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('n') if key.code == KeyCode::Down || ctrl => {
+                let count = tmux::search_panes(&self.sessions, query).len();
+                *selected = (*selected + 1).min(count.saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let found = tmux::search_panes(&self.sessions, query);
+                let Some(found) = found.get(*selected).or(found.last()).cloned() else {
+                    return Action::None;
+                };
+                self.mode = Mode::Normal;
+                self.select_pane_match(&found);
+                // Alt+Enter attaches, as in the new-session prompt
+                if key
+                    .modifiers
+                    .intersects(KeyModifiers::ALT | KeyModifiers::SHIFT)
+                {
+                    return self.action_attach(false);
+                }
             }
+            KeyCode::Backspace => {
+                query.pop();
+                *selected = 0;
+            }
+            KeyCode::Char(c) => {
+                query.push(c);
+                *selected = 0;
+            }
+            _ => {}
         }
         Action::None
     }
 
+    fn handle_menu_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::Menu { ref mut selected } = self.mode else {
+            return Action::None;
+        };
+
+        let item = match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char(',') => {
+                self.mode = Mode::Normal;
+                return Action::None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                *selected = selected.saturating_sub(1);
+                return Action::None;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                *selected = (*selected + 1).min(MenuItem::ALL.len() - 1);
+                return Action::None;
+            }
+            KeyCode::Enter => MenuItem::ALL[*selected],
+            KeyCode::Char(c) => match MenuItem::ALL.into_iter().find(|item| item.key() == c) {
+                Some(item) => item,
+                None => return Action::None,
+            },
+            _ => return Action::None,
+        };
+
+        // Actions that open their own popup replace the menu
+        self.mode = Mode::Normal;
+        match item {
+            MenuItem::AttachReadOnly => self.action_attach(true),
+            MenuItem::AttachInTerminal => self.action_attach_in_terminal(),
+            MenuItem::DuplicateSession => self.action_start_duplicate(),
+            MenuItem::EditNote => self.action_start_edit_note(),
+            MenuItem::DetachClients => self.action_detach_clients(),
+            MenuItem::ShowEnvironment => self.action_show_environment(),
+            MenuItem::ExportTemplate => self.action_export_template(),
+            MenuItem::MoveWindow => self.action_start_move(false),
+            MenuItem::LinkWindow => self.action_start_move(true),
+            MenuItem::RenumberWindows => self.action_renumber_windows(),
+            MenuItem::ToggleSync => self.action_toggle_sync(),
+            MenuItem::RespawnWindow => self.action_respawn_window(),
+            MenuItem::BreakPane => self.action_break_pane(),
+            MenuItem::RespawnPane => self.action_respawn_pane(),
+            MenuItem::PreviewPane => self.action_preview(),
+            MenuItem::CapturePane => self.action_capture_pane(),
+            MenuItem::CopyPath => self.action_copy_path(),
+            MenuItem::CopyAttachCommand => self.action_copy_attach_command(),
+            MenuItem::SessionsOnly => {
+                self.toggle_sessions_only();
+                Action::None
+            }
+            MenuItem::GroupSessions => {
+                self.toggle_grouping();
+                Action::None
+            }
+            MenuItem::AttachedOnly => {
+                self.toggle_show_detached();
+                Action::None
+            }
+            MenuItem::AllPanes => {
+                self.toggle_always_show_panes();
+                Action::None
+            }
+            MenuItem::Processes => {
+                self.show_processes = !self.show_processes;
+                Action::Refresh
+            }
+            MenuItem::Usage => {
+                self.show_usage = !self.show_usage;
+                Action::Refresh
+            }
+            MenuItem::ServerInfo => self.action_show_server_info(),
+            MenuItem::KillServer => {
+                self.mode = Mode::ConfirmKillServer {
+                    input: String::new(),
+                };
+                Action::None
+            }
+        }
+    }
+
+    /// Select a search result: the pane leaf if the tree lists it, else its window
+    fn select_pane_match(&mut self, found: &tmux::PaneMatch) {
+        let panes_listed = self
+            .sessions
+            .iter()
+            .find(|s| s.name == found.session)
+            .and_then(|s| s.windows.iter().find(|w| w.index == found.window))
+            .is_some_and(|w| w.panes.len() > 1 || self.always_show_panes);
+        let mut path = vec![found.session.clone(), found.window.to_string()];
+        if panes_listed {
+            path.push(found.pane.to_string());
+        }
+        self.select_path(path);
+    }
+
+    fn handle_edit_note_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::EditNote {
+            ref target,
+            ref mut input,
+        } = self.mode
+        else {
+            return Action::None;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => {
+                let target = target.clone();
+                let note = input.trim().to_string();
+                self.mode = Mode::Normal;
+                // An empty note clears it
+                if note.is_empty() {
+                    self.notes.remove(&target);
+                } else {
+                    self.notes.insert(target, note);
+                }
+                self.save_notes();
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn handle_move_window_key(&mut self, key: KeyEvent) -> Action {
+        let Mode::MoveWindow { ref src, link } = self.mode else {
+            return Action::None;
+        };
+        let src = src.clone();
+        let pending = self.pending_letter.take().map(|(first, _)| first);
+
+        // A letter that starts two-letter labels (AA, AB, …) waits for the
+        // second; Enter takes it alone. Any other key cancels.
+        let label = match (pending, key.code) {
+            (Some(first), KeyCode::Char(c)) if c.is_ascii_alphabetic() => format!("{first}{c}"),
+            (Some(first), KeyCode::Enter) => first.to_string(),
+            (None, KeyCode::Char(c)) if c.is_ascii_alphabetic() => {
+                if self.starts_two_letter_label(c) {
+                    self.pending_letter = Some((c, Instant::now()));
+                    return Action::None;
+                }
+                c.to_string()
+            }
+            _ => {
+                self.mode = Mode::Normal;
+                return Action::None;
+            }
+        };
+        self.mode = Mode::Normal;
+
+        // Labels index the sessions the tree shows, as in normal mode
+        let Some(dst) = ui::session_index(&label)
+            .and_then(|idx| self.visible_sessions().get(idx).map(|s| s.name.clone()))
+        else {
+            return Action::None;
+        };
+
+        let (result, verb) = if link {
+            (tmux::link_window(&src, &dst), "Linked")
+        } else {
+            (tmux::move_window(&src, &dst), "Moved")
+        };
+        match result {
+            Ok(()) => {
+                self.push_flash(FlashMessage::success(format!(
+                    "{verb} window '{src}' → '{dst}'"
+                )));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
     /// Determine attach target from current tree selection
-    fn action_attach(&mut self) -> Action {
-        let selected = self.tree_state.selected();
+    fn action_attach(&mut self, read_only: bool) -> Action {
+        let selected = self.selection();
         if selected.is_empty() {
             return Action::None;
         }
@@ -353,33 +1919,657 @@ impl App {
             _ => format!("{}:{}", selected[0], selected[1]),
         };
 
-        Action::Attach(target)
+        // Attaching lands on the session's last active window, so select
+        // the chosen window (and pane) first
+        let mut steps = Vec::new();
+        if let [session, window, rest @ ..] = selected.as_slice() {
+            steps.push(PreAttach::SelectWindow(format!("{session}:{window}")));
+            if let [pane] = rest {
+                steps.push(PreAttach::SelectPane(format!("{session}:{window}.{pane}")));
+            }
+        }
+
+        Action::Attach(AttachRequest {
+            target,
+            read_only,
+            steps,
+        })
+    }
+
+    /// Attach in a new terminal window, leaving tmxu running.
+    /// Without `--terminal` this is a plain attach.
+    fn action_attach_in_terminal(&mut self) -> Action {
+        let Some(terminal) = self.terminal.clone() else {
+            return self.action_attach(false);
+        };
+        let Action::Attach(request) = self.action_attach(false) else {
+            return Action::None;
+        };
+
+        for step in &request.steps {
+            // As in a plain attach: the session's current window beats no terminal
+            if let Err(e) = step.run() {
+                self.report_error(e);
+            }
+        }
+        match spawn_in_terminal(&terminal, &request) {
+            Ok(()) => self.push_flash(FlashMessage::success(format!(
+                "Opened '{}' in a new terminal",
+                request.target
+            ))),
+            Err(e) => self.report_error(e),
+        }
+        Action::None
     }
 
     /// Start kill confirmation for the selected session
     fn action_start_kill(&mut self) -> Action {
-        let selected = self.tree_state.selected();
+        let selected = self.selection();
         if selected.is_empty() {
             return Action::None;
         }
         let session_name = selected[0].clone();
+        if !self.confirm_kill {
+            return self.kill_session(&session_name);
+        }
+        let (windows, panes) = self
+            .sessions
+            .iter()
+            .find(|s| s.name == session_name)
+            .map_or((0, 0), |s| (s.windows.len(), s.pane_count()));
         self.mode = Mode::ConfirmKill {
             target: session_name,
+            windows,
+            panes,
         };
         Action::None
     }
 
-    /// Start rename for the selected session
-    fn action_start_rename(&mut self) -> Action {
-        let selected = self.tree_state.selected();
+    /// Start rename for the selected session, in a popup or with `inline`
+    /// on the session's own row (which gets selected so it is in view)
+    fn action_start_rename(&mut self, inline: bool) -> Action {
+        let selected = self.selection();
         if selected.is_empty() {
             return Action::None;
         }
         let session_name = selected[0].clone();
+        if inline {
+            self.select_path(vec![session_name.clone()]);
+        }
         self.mode = Mode::RenameSession {
             target: session_name.clone(),
             input: session_name,
+            error: None,
+            inline,
+        };
+        Action::None
+    }
+
+    /// Start creating a session rooted where the selected session's active pane is,
+    /// with the name prompt seeded as `<orig>-copy`
+    fn action_start_duplicate(&mut self) -> Action {
+        let selected = self.selection();
+        let Some(session) = self
+            .sessions
+            .iter()
+            .find(|s| Some(&s.name) == selected.first())
+        else {
+            return Action::None;
+        };
+        let start_dir = session
+            .windows
+            .iter()
+            .find(|w| w.active)
+            .or(session.windows.first())
+            .and_then(|w| w.active_pane())
+            .map(|p| p.current_path.clone());
+        self.mode = Mode::CreateSession {
+            input: format!("{}-copy", session.name),
+            start_dir,
+            error: None,
+        };
+        Action::None
+    }
+
+    /// Start editing the selected session's note
+    fn action_start_edit_note(&mut self) -> Action {
+        let selected = self.selection();
+        if selected.is_empty() {
+            return Action::None;
+        }
+        let target = selected[0].clone();
+        let input = self.notes.get(&target).cloned().unwrap_or_default();
+        self.mode = Mode::EditNote { target, input };
+        Action::None
+    }
+
+    /// Persist `notes`, flashing if the file can't be written
+    fn save_notes(&mut self) {
+        if let Err(e) = store::save(NOTES_FILE, &self.notes) {
+            self.push_flash(FlashMessage::error(format!("Failed to save notes: {e}")));
+        }
+    }
+
+    /// Pin or unpin the selected session, moving it to its new place in the list
+    fn action_toggle_pin(&mut self) -> Action {
+        let selected = self.selection();
+        let Some(name) = selected.first().cloned() else {
+            return Action::None;
+        };
+        if !self.pinned.remove(&name) {
+            self.pinned.insert(name.clone());
+        }
+        pinned_first(&mut self.sessions, &self.pinned);
+        self.select_path(selected);
+        self.save_pinned();
+        Action::None
+    }
+
+    /// Persist `pinned`, flashing if the file can't be written
+    fn save_pinned(&mut self) {
+        if let Err(e) = store::save(PINNED_FILE, &self.pinned) {
+            self.push_flash(FlashMessage::error(format!("Failed to save pins: {e}")));
+        }
+    }
+
+    /// Start picking a destination session for the selected window
+    fn action_start_move(&mut self, link: bool) -> Action {
+        let selected = self.selection();
+        if selected.len() < 2 {
+            let verb = if link { "link" } else { "move" };
+            self.push_flash(FlashMessage::info(format!("Select a window to {verb}")));
+            return Action::None;
+        }
+        self.mode = Mode::MoveWindow {
+            src: format!("{}:{}", selected[0], selected[1]),
+            link,
+        };
+        Action::None
+    }
+
+    /// Swap the selected window with the next (or previous) window in its session,
+    /// keeping the selection on the moved window so repeated presses keep dragging it
+    fn action_swap_window(&mut self, forward: bool) -> Action {
+        let selected = self.selection();
+        if selected.len() < 2 {
+            return Action::None;
+        }
+        let session_name = selected[0].clone();
+        let Ok(index) = selected[1].parse::<u32>() else {
+            return Action::None;
+        };
+
+        let Some(session) = self.sessions.iter_mut().find(|s| s.name == session_name) else {
+            return Action::None;
+        };
+        let Some(pos) = session.windows.iter().position(|w| w.index == index) else {
+            return Action::None;
+        };
+        let other_pos = if forward {
+            pos + 1
+        } else {
+            match pos.checked_sub(1) {
+                Some(p) => p,
+                None => return Action::None,
+            }
+        };
+        let Some(other_index) = session.windows.get(other_pos).map(|w| w.index) else {
+            return Action::None;
+        };
+
+        match tmux::swap_window(&session_name, index, other_index) {
+            Ok(()) => {
+                // Mirror the swap locally so the tree updates before the refresh lands
+                let windows = &mut session.windows;
+                windows.swap(pos, other_pos);
+                let (a, b) = windows.split_at_mut(pos.max(other_pos));
+                std::mem::swap(&mut a[pos.min(other_pos)].index, &mut b[0].index);
+
+                self.select_path(vec![session_name, other_index.to_string()]);
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    /// Open a popup with the selected session's environment
+    fn action_show_environment(&mut self) -> Action {
+        let Some(session) = self.selection().first().cloned() else {
+            return Action::None;
         };
+        match tmux::show_environment(&session) {
+            Ok(vars) => {
+                self.mode = Mode::Environment {
+                    session,
+                    vars,
+                    scroll: 0,
+                }
+            }
+            Err(e) => self.report_error(e),
+        }
         Action::None
     }
+
+    /// Flash the server's pid and the config files it loaded
+    fn action_show_server_info(&mut self) -> Action {
+        match tmux::server_info() {
+            Ok(info) => {
+                let config = if info.config_files.is_empty() {
+                    "no config file loaded".to_string()
+                } else {
+                    let files: Vec<String> = info
+                        .config_files
+                        .iter()
+                        .map(|f| tmux::shorten_path(f))
+                        .collect();
+                    format!("config: {}", files.join(", "))
+                };
+                let uptime = ui::unix_now().saturating_sub(info.start_time);
+                self.push_flash(FlashMessage::info(format!(
+                    "tmux server pid {} · up {} · {config}",
+                    info.pid,
+                    ui::format_age(uptime)
+                )));
+            }
+            Err(e) => self.report_error(e),
+        }
+        Action::None
+    }
+
+    /// Detach every client attached to the selected session
+    fn action_detach_clients(&mut self) -> Action {
+        let Some(session) = self
+            .selection()
+            .first()
+            .and_then(|name| self.sessions.iter().find(|s| &s.name == name))
+        else {
+            return Action::None;
+        };
+        let name = session.name.clone();
+        if !session.attached {
+            self.push_flash(FlashMessage::info(format!(
+                "No clients attached to '{name}'"
+            )));
+            return Action::None;
+        }
+        match tmux::detach_clients(&name) {
+            Ok(()) => {
+                self.push_flash(FlashMessage::success(format!(
+                    "Detached clients of '{name}'"
+                )));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    /// Renumber the selected session's windows so their indices have no gaps
+    fn action_renumber_windows(&mut self) -> Action {
+        let selected = self.selection();
+        let Some(session_name) = selected.first().cloned() else {
+            return Action::None;
+        };
+        let before: Vec<u32> = match self.sessions.iter().find(|s| s.name == session_name) {
+            Some(session) => session.windows.iter().map(|w| w.index).collect(),
+            None => return Action::None,
+        };
+
+        if let Err(e) = tmux::renumber_windows(&session_name) {
+            self.report_error(e);
+            return Action::None;
+        }
+
+        // The selected window may have a new index, so fall back to its session
+        self.select_path(vec![session_name.clone()]);
+        match tmux::window_indices(&session_name) {
+            Ok(after) => {
+                let moved = before.iter().zip(&after).filter(|(b, a)| b != a).count();
+                let message = if moved == 0 {
+                    format!("Windows in '{session_name}' were already numbered in order")
+                } else {
+                    format!(
+                        "Renumbered {moved} of {} windows in '{session_name}' ({} → {})",
+                        after.len(),
+                        join_indices(&before),
+                        join_indices(&after)
+                    )
+                };
+                self.push_flash(FlashMessage::success(message));
+            }
+            Err(e) => self.report_error(e),
+        }
+        Action::Refresh
+    }
+
+    /// Break the selected pane out into its own window
+    fn action_break_pane(&mut self) -> Action {
+        let selected = self.selection();
+        // Only pane leaves (session, window, pane) can be broken out
+        if selected.len() != 3 {
+            self.push_flash(FlashMessage::info("Select a pane to break out"));
+            return Action::None;
+        }
+        let target = format!("{}:{}.{}", selected[0], selected[1], selected[2]);
+
+        match tmux::break_pane(&target) {
+            Ok(()) => {
+                self.push_flash(FlashMessage::success(format!(
+                    "Broke pane '{target}' into a new window"
+                )));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    /// Restart the selected dead pane (a selected window means its active pane)
+    fn action_respawn_pane(&mut self) -> Action {
+        let selected = self.selection();
+        if selected.len() < 2 {
+            self.push_flash(FlashMessage::info("Select a pane to respawn"));
+            return Action::None;
+        }
+        let Some((index, dead)) = self.selected_pane().map(|p| (p.index, p.dead)) else {
+            return Action::None;
+        };
+        let target = format!("{}:{}.{}", selected[0], selected[1], index);
+        // -k would kill a live process; only dead panes are fair game
+        if !dead {
+            self.push_flash(FlashMessage::info(format!(
+                "Pane '{target}' is still running"
+            )));
+            return Action::None;
+        }
+
+        match tmux::respawn_pane(&target) {
+            Ok(()) => {
+                self.push_flash(FlashMessage::success(format!("Respawned pane '{target}'")));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    /// Restart the selected window if it has a dead pane. This resets it to one pane.
+    fn action_respawn_window(&mut self) -> Action {
+        let selected = self.selection();
+        if selected.len() < 2 {
+            self.push_flash(FlashMessage::info("Select a window to respawn"));
+            return Action::None;
+        }
+        let has_dead = self
+            .sessions
+            .iter()
+            .find(|s| s.name == selected[0])
+            .and_then(|s| {
+                s.windows
+                    .iter()
+                    .find(|w| w.index.to_string() == selected[1])
+            })
+            .is_some_and(|w| w.panes.iter().any(|p| p.dead));
+        let target = format!("{}:{}", selected[0], selected[1]);
+        if !has_dead {
+            self.push_flash(FlashMessage::info(format!(
+                "Window '{target}' has no dead panes"
+            )));
+            return Action::None;
+        }
+
+        match tmux::respawn_window(&target) {
+            Ok(()) => {
+                self.push_flash(FlashMessage::success(format!(
+                    "Respawned window '{target}'"
+                )));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    /// Toggle `synchronize-panes` on the selected window
+    fn action_toggle_sync(&mut self) -> Action {
+        let selected = self.selection();
+        if selected.len() < 2 {
+            self.push_flash(FlashMessage::info("Select a window to synchronize"));
+            return Action::None;
+        }
+        let on = !self
+            .sessions
+            .iter()
+            .find(|s| s.name == selected[0])
+            .and_then(|s| {
+                s.windows
+                    .iter()
+                    .find(|w| w.index.to_string() == selected[1])
+            })
+            .is_some_and(|w| w.synchronized);
+        let target = format!("{}:{}", selected[0], selected[1]);
+
+        match tmux::set_synchronize_panes(&target, on) {
+            Ok(()) => {
+                let state = if on { "on" } else { "off" };
+                self.push_flash(FlashMessage::success(format!(
+                    "Synchronize panes {state} for '{target}'"
+                )));
+                Action::Refresh
+            }
+            Err(e) => {
+                self.report_error(e);
+                Action::None
+            }
+        }
+    }
+
+    /// Start typing a command for the selected pane (or a window's active pane)
+    fn action_start_send_keys(&mut self) -> Action {
+        let selected = self.selection();
+        let target = match selected.len() {
+            0 => return Action::None,
+            1 => selected[0].clone(),
+            2 => format!("{}:{}", selected[0], selected[1]),
+            _ => format!("{}:{}.{}", selected[0], selected[1], selected[2]),
+        };
+        self.mode = Mode::SendKeys {
+            target,
+            input: String::new(),
+        };
+        Action::None
+    }
+
+    /// The selected pane; a selected window or session resolves to its active pane
+    fn selected_pane(&self) -> Option<&TmuxPane> {
+        let selected = self.selection();
+        let session = self
+            .sessions
+            .iter()
+            .find(|s| Some(&s.name) == selected.first())?;
+        let window = match selected.get(1) {
+            Some(index) => session
+                .windows
+                .iter()
+                .find(|w| &w.index.to_string() == index)?,
+            None => session
+                .windows
+                .iter()
+                .find(|w| w.active)
+                .or(session.windows.first())?,
+        };
+        match selected.get(2) {
+            Some(index) => window.panes.iter().find(|p| &p.index.to_string() == index),
+            None => window.active_pane(),
+        }
+    }
+
+    /// Save the selected pane's entire scrollback (a window's active pane) to a file
+    fn action_capture_pane(&mut self) -> Action {
+        let selected = self.selection();
+        let target = match selected.len() {
+            0 | 1 => {
+                self.push_flash(FlashMessage::info("Select a window or pane to capture"));
+                return Action::None;
+            }
+            2 => format!("{}:{}", selected[0], selected[1]),
+            _ => format!("{}:{}.{}", selected[0], selected[1], selected[2]),
+        };
+
+        let saved = tmux::capture_pane_full(&target)
+            .and_then(|text| write_capture(&selected[0], &selected[1], &text));
+        match saved {
+            Ok(path) => self.push_flash(FlashMessage::success(format!(
+                "Saved {}",
+                tmux::shorten_path(&path.to_string_lossy())
+            ))),
+            Err(e) => self.push_flash(FlashMessage::error(format!("Capture failed: {e}"))),
+        }
+        Action::None
+    }
+
+    /// Write the selected session out as a template `--load` can rebuild
+    fn action_export_template(&mut self) -> Action {
+        let Some(session) = self
+            .selection()
+            .first()
+            .and_then(|name| self.sessions.iter().find(|s| &s.name == name))
+        else {
+            return Action::None;
+        };
+        let yaml = template::to_yaml(&template::from_session(session));
+        match write_template(&session.name, &yaml) {
+            Ok(path) => self.push_flash(FlashMessage::success(format!(
+                "Saved {}",
+                tmux::shorten_path(&path.to_string_lossy())
+            ))),
+            Err(e) => self.push_flash(FlashMessage::error(format!("Export failed: {e}"))),
+        }
+        Action::None
+    }
+
+    /// Show the last lines of the selected pane (or a window's active pane)
+    fn action_preview(&mut self) -> Action {
+        let selected = self.selection();
+        let target = match selected.len() {
+            0 | 1 => {
+                self.push_flash(FlashMessage::info("Select a window or pane to preview"));
+                return Action::None;
+            }
+            2 => format!("{}:{}", selected[0], selected[1]),
+            _ => format!("{}:{}.{}", selected[0], selected[1], selected[2]),
+        };
+        self.open_preview(target);
+        Action::None
+    }
+
+    fn open_preview(&mut self, target: String) {
+        match tmux::capture_pane(&target, self.preview_lines) {
+            Ok(text) => self.mode = Mode::Preview { target, text },
+            Err(e) => {
+                self.mode = Mode::Normal;
+                self.report_error(e);
+            }
+        }
+    }
+
+    /// Copy a shell command that attaches to the selection, with the same
+    /// socket flags and target an in-app attach would use
+    fn action_copy_attach_command(&mut self) -> Action {
+        let Action::Attach(request) = self.action_attach(false) else {
+            return Action::None;
+        };
+        let command = tmux::command_line(&request.command(false));
+        match clipboard::copy(&command) {
+            Ok(()) => self.push_flash(FlashMessage::success(format!("Copied {command}"))),
+            Err(e) => self.push_flash(FlashMessage::error(format!("Copy failed: {e}"))),
+        }
+        Action::None
+    }
+
+    /// Copy the selected pane's full working directory to the clipboard (OSC 52)
+    fn action_copy_path(&mut self) -> Action {
+        let Some(path) = self.selected_pane().map(|p| p.current_path.clone()) else {
+            return Action::None;
+        };
+        match clipboard::copy(&path) {
+            Ok(()) => self.push_flash(FlashMessage::success(format!(
+                "Copied {}",
+                tmux::shorten_path(&path)
+            ))),
+            Err(e) => self.push_flash(FlashMessage::error(format!("Copy failed: {e}"))),
+        }
+        Action::None
+    }
+}
+
+/// Stable-partition pinned sessions to the front, keeping tmux's order otherwise
+fn pinned_first(sessions: &mut [TmuxSession], pinned: &HashSet<String>) {
+    sessions.sort_by_key(|s| !pinned.contains(&s.name));
+}
+
+/// Write captured pane text to `~/tmxu-capture-<session>-<window>-<unix time>.txt`
+fn write_capture(session: &str, window: &str, text: &str) -> Result<PathBuf> {
+    let dir = std::env::var_os("HOME").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    // Session names may hold '/' (see grouping), which can't go in a file name
+    let session = session.replace('/', "_");
+    let path = dir.join(format!(
+        "tmxu-capture-{session}-{window}-{}.txt",
+        ui::unix_now()
+    ));
+    std::fs::write(&path, text).wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Write a session template to `~/tmxu-template-<session>-<unix time>.yaml`
+fn write_template(session: &str, yaml: &str) -> Result<PathBuf> {
+    let dir = std::env::var_os("HOME").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let session = session.replace('/', "_");
+    let path = dir.join(format!("tmxu-template-{session}-{}.yaml", ui::unix_now()));
+    std::fs::write(&path, yaml).wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Start `terminal` (split on whitespace) running the attach for `request`,
+/// without waiting for it; a thread reaps it so it doesn't linger as a zombie
+fn spawn_in_terminal(terminal: &str, request: &AttachRequest) -> Result<()> {
+    let mut words = terminal.split_whitespace();
+    let program = words.next().ok_or_else(|| eyre!("--terminal is empty"))?;
+    let attach = request.command(false);
+
+    let mut child = Command::new(program)
+        .args(words)
+        .arg(attach.get_program())
+        .args(attach.get_args())
+        // The new client isn't nested in this one, so tmux mustn't think it is
+        .env_remove("TMUX")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .wrap_err_with(|| format!("Failed to start {program}"))?;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Space-separated window indices for flash messages, e.g. "1 4 7"
+fn join_indices(indices: &[u32]) -> String {
+    indices
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
 }
@@ -0,0 +1,607 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Report, Result};
+
+use crate::completions::Shell;
+use crate::tmux::Socket;
+
+/// What a flag's value completes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// Boolean switch, takes no value
+    None,
+    /// Free-form text
+    Text,
+    /// A filesystem path
+    File,
+    /// The name of a live tmux session
+    Session,
+}
+
+/// Order of windows within each session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowSort {
+    /// By window index, as tmux numbers them
+    #[default]
+    Index,
+    /// Most recently active first
+    Activity,
+}
+
+impl FromStr for WindowSort {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "index" => Ok(WindowSort::Index),
+            "activity" => Ok(WindowSort::Activity),
+            _ => Err(eyre!(
+                "unknown window sort '{s}' (expected index or activity)"
+            )),
+        }
+    }
+}
+
+/// Where the status bar goes; the banner takes the opposite end
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusPosition {
+    /// Banner on top, status bar at the bottom
+    #[default]
+    Bottom,
+    /// Status bar on top, banner at the bottom, for terminals whose own
+    /// status line is already at the bottom
+    Top,
+}
+
+impl FromStr for StatusPosition {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bottom" => Ok(StatusPosition::Bottom),
+            "top" => Ok(StatusPosition::Top),
+            _ => Err(eyre!("unknown layout '{s}' (expected top or bottom)")),
+        }
+    }
+}
+
+/// Symbols the tree draws before rows: node markers and the selection marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSymbols {
+    pub closed: String,
+    pub open: String,
+    /// Before rows with no children
+    pub leaf: String,
+    /// Before the selected row
+    pub highlight: String,
+}
+
+impl Default for TreeSymbols {
+    fn default() -> Self {
+        Self::new("▸ ", "▾ ", "  ", ">> ")
+    }
+}
+
+impl TreeSymbols {
+    /// Pad the node symbols to one width, so rows line up whichever is drawn
+    fn new(closed: &str, open: &str, leaf: &str, highlight: &str) -> Self {
+        let width = [closed, open, leaf]
+            .iter()
+            .map(|s| s.chars().count())
+            .max()
+            .unwrap_or(0);
+        let pad = |s: &str| format!("{s:<width$}");
+        Self {
+            closed: pad(closed),
+            open: pad(open),
+            leaf: pad(leaf),
+            highlight: highlight.to_string(),
+        }
+    }
+
+    /// Columns the symbols take before a row's text at the top level
+    pub fn width(&self) -> usize {
+        self.highlight.chars().count() + self.closed.chars().count()
+    }
+}
+
+impl FromStr for TreeSymbols {
+    type Err = Report;
+
+    /// `unicode`, `ascii`, or four comma-separated symbols:
+    /// closed, open, leaf and selected, e.g. `+ ,- ,  ,> `
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unicode" => Ok(Self::default()),
+            "ascii" => Ok(Self::new("+ ", "- ", "  ", "> ")),
+            _ => match s.split(',').collect::<Vec<_>>()[..] {
+                [closed, open, leaf, highlight] => Ok(Self::new(closed, open, leaf, highlight)),
+                _ => Err(eyre!(
+                    "invalid symbols '{s}' (expected unicode, ascii or closed,open,leaf,selected)"
+                )),
+            },
+        }
+    }
+}
+
+/// A command-line flag, as advertised to shell completions
+pub struct Flag {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: ValueKind,
+    pub help: &'static str,
+}
+
+/// Every flag tmxu accepts. Keep in sync with `Args::parse_from`.
+pub const FLAGS: &[Flag] = &[
+    Flag {
+        long: "version",
+        short: Some('V'),
+        value: ValueKind::None,
+        help: "Print the tmxu and tmux versions and exit",
+    },
+    Flag {
+        long: "no-logo",
+        short: None,
+        value: ValueKind::None,
+        help: "Hide the hostname banner",
+    },
+    Flag {
+        long: "hash-colors",
+        short: None,
+        value: ValueKind::None,
+        help: "Color session names by a hash of the name",
+    },
+    Flag {
+        long: "fish-paths",
+        short: None,
+        value: ValueKind::None,
+        help: "Abbreviate window paths fish-style (~/c/p/tmxu)",
+    },
+    Flag {
+        long: "window-format",
+        short: None,
+        value: ValueKind::Text,
+        help: "Window summary template: {command} {path} {panes} {branch}",
+    },
+    Flag {
+        long: "window-sort",
+        short: None,
+        value: ValueKind::Text,
+        help: "Order windows by index or activity",
+    },
+    Flag {
+        long: "layout",
+        short: None,
+        value: ValueKind::Text,
+        help: "Put the status bar at the top or bottom (default bottom)",
+    },
+    Flag {
+        long: "symbols",
+        short: None,
+        value: ValueKind::Text,
+        help: "Tree symbols: unicode, ascii or closed,open,leaf,selected",
+    },
+    Flag {
+        long: "command-names",
+        short: None,
+        value: ValueKind::None,
+        help: "Show the running command for windows named after your shell",
+    },
+    Flag {
+        long: "expand-all",
+        short: None,
+        value: ValueKind::None,
+        help: "Start with every session expanded",
+    },
+    Flag {
+        long: "all-panes",
+        short: None,
+        value: ValueKind::None,
+        help: "List panes of single-pane windows too",
+    },
+    Flag {
+        long: "preview-lines",
+        short: None,
+        value: ValueKind::Text,
+        help: "Lines shown in the pane preview (, V); +/- adjust it live",
+    },
+    Flag {
+        long: "confirm-quit",
+        short: None,
+        value: ValueKind::None,
+        help: "Ask before q/Esc quits",
+    },
+    Flag {
+        long: "no-confirm-kill",
+        short: None,
+        value: ValueKind::None,
+        help: "Kill sessions with d without asking",
+    },
+    Flag {
+        long: "clock",
+        short: None,
+        value: ValueKind::None,
+        help: "Show the time and tmux server uptime in the status bar",
+    },
+    Flag {
+        long: "json",
+        short: None,
+        value: ValueKind::None,
+        help: "Print sessions as JSON and exit",
+    },
+    Flag {
+        long: "watch",
+        short: None,
+        value: ValueKind::None,
+        help: "Print the session tree every refresh interval without the TUI",
+    },
+    Flag {
+        long: "refresh-interval",
+        short: None,
+        value: ValueKind::Text,
+        help: "How often to refresh sessions (default 2s)",
+    },
+    Flag {
+        long: "flash-duration",
+        short: None,
+        value: ValueKind::Text,
+        help: "How long status messages stay up (default 3s)",
+    },
+    Flag {
+        long: "error-duration",
+        short: None,
+        value: ValueKind::Text,
+        help: "How long error messages stay up (default 6s)",
+    },
+    Flag {
+        long: "attach",
+        short: None,
+        value: ValueKind::Session,
+        help: "Attach to a session without the TUI",
+    },
+    Flag {
+        long: "create",
+        short: None,
+        value: ValueKind::Text,
+        help: "Create a detached session and exit (with --attach, attach to it)",
+    },
+    Flag {
+        long: "load",
+        short: None,
+        value: ValueKind::File,
+        help: "Build a session from a tmuxp-style YAML template and exit",
+    },
+    Flag {
+        long: "terminal",
+        short: None,
+        value: ValueKind::Text,
+        help: "Terminal command that , T attaches in, e.g. 'alacritty -e'",
+    },
+    Flag {
+        long: "ensure",
+        short: None,
+        value: ValueKind::Session,
+        help: "Attach to a session, creating it first if needed",
+    },
+    Flag {
+        long: "kill-idle",
+        short: None,
+        value: ValueKind::Text,
+        help: "Kill sessions idle longer than a duration (30m, 2h, 1d)",
+    },
+    Flag {
+        long: "yes",
+        short: None,
+        value: ValueKind::None,
+        help: "Don't ask for confirmation",
+    },
+    Flag {
+        long: "dry-run",
+        short: None,
+        value: ValueKind::None,
+        help: "Print tmux commands that would change anything instead of running them",
+    },
+    Flag {
+        long: "log",
+        short: None,
+        value: ValueKind::File,
+        help: "Append a debug log of tmux invocations to a file",
+    },
+    Flag {
+        long: "socket-name",
+        short: Some('L'),
+        value: ValueKind::Text,
+        help: "tmux server socket name",
+    },
+    Flag {
+        long: "socket-path",
+        short: Some('S'),
+        value: ValueKind::File,
+        help: "tmux server socket path",
+    },
+    Flag {
+        long: "tmux-path",
+        short: None,
+        value: ValueKind::File,
+        help: "tmux binary to run",
+    },
+];
+
+/// Parsed command-line options
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Print version information and exit
+    pub version: bool,
+    pub no_logo: bool,
+    /// Color each session name by a hash of the name instead of uniform cyan
+    pub hash_colors: bool,
+    /// Abbreviate intermediate directories in window paths
+    pub fish_paths: bool,
+    /// List the pane of single-pane windows too
+    pub all_panes: bool,
+    /// Label windows still named after the login shell by their active command
+    pub command_names: bool,
+    /// Open every session node at startup
+    pub expand_all: bool,
+    /// Template for the summary shown after each window name
+    pub window_format: Option<String>,
+    /// Order of windows within each session
+    pub window_sort: WindowSort,
+    /// Where the status bar goes
+    pub layout: StatusPosition,
+    /// Node and selection markers in the tree
+    pub symbols: TreeSymbols,
+    /// Lines of pane output in the preview popup
+    pub preview_lines: Option<u16>,
+    /// Ask for confirmation before `q`/Esc quits
+    pub confirm_quit: bool,
+    /// Kill sessions with `d` straight away instead of asking
+    pub no_confirm_kill: bool,
+    /// Show the time and server uptime in the status bar
+    pub clock: bool,
+    /// Print sessions as JSON and exit instead of starting the TUI
+    pub json: bool,
+    /// Reprint the session tree to stdout every refresh interval
+    pub watch: bool,
+    /// Override how often sessions are refreshed
+    pub refresh_interval: Option<Duration>,
+    /// How long info and success flash messages stay up
+    pub flash_duration: Option<Duration>,
+    /// How long error flash messages stay up
+    pub error_duration: Option<Duration>,
+    /// Attach straight to this session without starting the TUI
+    pub attach: Option<String>,
+    /// Create this detached session and exit, or attach with `attach`
+    pub create: Option<String>,
+    /// Build a session from this template file and exit, or attach with `attach`
+    pub load: Option<PathBuf>,
+    /// Like `attach`, but create the session first if it doesn't exist
+    pub ensure: Option<String>,
+    /// Terminal emulator command (e.g. `alacritty -e`) that `,` `T` opens attaches in
+    pub terminal: Option<String>,
+    /// Kill every session idle for longer than this, then exit
+    pub kill_idle: Option<Duration>,
+    /// Skip confirmation prompts in non-interactive modes
+    pub yes: bool,
+    /// Record state-changing tmux commands instead of running them
+    pub dry_run: bool,
+    /// Log file for troubleshooting
+    pub log: Option<PathBuf>,
+    pub socket: Option<Socket>,
+    pub tmux_path: Option<String>,
+    /// `tmxu completions <shell>`: print a completion script and exit
+    pub completions: Option<Shell>,
+    /// `tmxu sessions`: print session names, one per line, and exit; what the
+    /// completion scripts call, with any socket flags from the command line
+    pub sessions: bool,
+}
+
+impl Args {
+    /// Parse the process arguments
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| eyre!("{flag} requires a value"))
+            };
+
+            match flag.as_str() {
+                "-V" | "--version" => parsed.version = true,
+                "--no-logo" => parsed.no_logo = true,
+                "--hash-colors" => parsed.hash_colors = true,
+                "--fish-paths" => parsed.fish_paths = true,
+                "--all-panes" => parsed.all_panes = true,
+                "--command-names" => parsed.command_names = true,
+                "--expand-all" => parsed.expand_all = true,
+                "--window-format" => parsed.window_format = Some(value()?),
+                "--window-sort" => parsed.window_sort = value()?.parse()?,
+                "--layout" => parsed.layout = value()?.parse()?,
+                "--symbols" => parsed.symbols = value()?.parse()?,
+                "--preview-lines" => {
+                    let lines = value()?;
+                    let lines = lines
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| eyre!("invalid --preview-lines '{lines}'"))?;
+                    parsed.preview_lines = Some(lines);
+                }
+                "--confirm-quit" => parsed.confirm_quit = true,
+                "--no-confirm-kill" => parsed.no_confirm_kill = true,
+                "--clock" => parsed.clock = true,
+                "--json" => parsed.json = true,
+                "--watch" => parsed.watch = true,
+                "--refresh-interval" => {
+                    let interval = parse_duration(&value()?)?;
+                    if interval.is_zero() {
+                        return Err(eyre!("--refresh-interval must be greater than zero"));
+                    }
+                    parsed.refresh_interval = Some(interval);
+                }
+                "--flash-duration" => {
+                    parsed.flash_duration = Some(parse_nonzero_duration(&flag, &value()?)?)
+                }
+                "--error-duration" => {
+                    parsed.error_duration = Some(parse_nonzero_duration(&flag, &value()?)?)
+                }
+                "--attach" => parsed.attach = Some(value()?),
+                "--create" => parsed.create = Some(value()?),
+                "--load" => parsed.load = Some(value()?.into()),
+                "--ensure" => parsed.ensure = Some(value()?),
+                "--terminal" => parsed.terminal = Some(value()?),
+                "--kill-idle" => parsed.kill_idle = Some(parse_duration(&value()?)?),
+                "--yes" => parsed.yes = true,
+                "--dry-run" => parsed.dry_run = true,
+                "--log" => parsed.log = Some(value()?.into()),
+                "-L" | "--socket-name" => parsed.socket = Some(Socket::Name(value()?)),
+                "-S" | "--socket-path" => parsed.socket = Some(Socket::Path(value()?)),
+                "--tmux-path" => parsed.tmux_path = Some(value()?),
+                "completions" => parsed.completions = Some(value()?.parse()?),
+                "sessions" => parsed.sessions = true,
+                _ => return Err(eyre!("unknown argument '{flag}'")),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Parse a duration like `30s`, `30m`, `2h` or `1d`
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| eyre!("invalid duration '{s}' (expected e.g. 30m, 2h, 1d)"))?;
+    let secs = match unit {
+        "s" => n,
+        "m" => n.saturating_mul(60),
+        "h" => n.saturating_mul(60 * 60),
+        "d" => n.saturating_mul(60 * 60 * 24),
+        _ => {
+            return Err(eyre!(
+                "invalid duration unit '{unit}' (expected s, m, h or d)"
+            ))
+        }
+    };
+    Ok(Duration::from_secs(secs))
+}
+
+/// `parse_duration` for `flag`, which can't be zero
+fn parse_nonzero_duration(flag: &str, s: &str) -> Result<Duration> {
+    let duration = parse_duration(s)?;
+    if duration.is_zero() {
+        return Err(eyre!("{flag} must be greater than zero"));
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse_from(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_parse_socket_flags() {
+        let args = parse(&["--no-logo", "-L", "work"]).unwrap();
+        assert!(args.no_logo);
+        assert_eq!(args.socket, Some(Socket::Name("work".into())));
+
+        let args = parse(&["--socket-path=/tmp/tmux.sock"]).unwrap();
+        assert_eq!(args.socket, Some(Socket::Path("/tmp/tmux.sock".into())));
+    }
+
+    #[test]
+    fn test_flags_table_matches_parser() {
+        for flag in FLAGS {
+            let mut args = vec![format!("--{}", flag.long)];
+            if flag.value != ValueKind::None {
+                // A value every flag's parser accepts, except the enumerated ones
+                let value = match flag.long {
+                    "window-sort" => "activity",
+                    "layout" => "top",
+                    "symbols" => "ascii",
+                    "preview-lines" => "20",
+                    _ => "1h",
+                };
+                args.push(value.into());
+            }
+            assert!(Args::parse_from(args).is_ok(), "--{} rejected", flag.long);
+        }
+    }
+
+    #[test]
+    fn test_parse_completions() {
+        let args = parse(&["completions", "zsh"]).unwrap();
+        assert_eq!(args.completions, Some(Shell::Zsh));
+        assert!(parse(&["completions", "tcsh"]).is_err());
+
+        let args = parse(&["-L", "work", "sessions"]).unwrap();
+        assert!(args.sessions);
+        assert_eq!(args.socket, Some(Socket::Name("work".into())));
+    }
+
+    #[test]
+    fn test_parse_symbols() {
+        let ascii: TreeSymbols = "ascii".parse().unwrap();
+        assert_eq!(ascii.closed, "+ ");
+        assert_eq!(ascii.width(), 4);
+        assert_eq!(TreeSymbols::default().width(), 5);
+
+        // Node symbols are padded to the widest so rows stay aligned
+        let custom: TreeSymbols = "[+],-,,*".parse().unwrap();
+        assert_eq!(
+            (
+                custom.closed.as_str(),
+                custom.open.as_str(),
+                custom.leaf.as_str()
+            ),
+            ("[+]", "-  ", "   ")
+        );
+        assert_eq!(custom.width(), 4);
+        assert!("+,-".parse::<TreeSymbols>().is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("90").is_err());
+        assert!(parse_duration("5w").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&["--socket-name"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["--refresh-interval", "0s"]).is_err());
+        assert!(parse(&["--error-duration", "0s"]).is_err());
+        assert!(parse(&["--window-sort", "name"]).is_err());
+        assert!(parse(&["--layout", "left"]).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use std::io::{self, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Copy `text` to the system clipboard with an OSC 52 escape sequence.
+/// Works over SSH and inside tmux (with `set-clipboard on`), since the
+/// terminal emulator does the copying.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(osc52(text).as_bytes())?;
+    stdout.flush()
+}
+
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(osc52("/tmp"), "\x1b]52;c;L3RtcA==\x07");
+    }
+}
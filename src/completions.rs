@@ -0,0 +1,225 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use color_eyre::eyre::{eyre, Report};
+
+use crate::cli::{ValueKind, FLAGS};
+
+/// Shells `tmxu completions` can generate a script for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(eyre!(
+                "unsupported shell '{s}' (expected bash, zsh or fish)"
+            )),
+        }
+    }
+}
+
+/// Flags that pick the tmux server; completing `--attach` forwards them to
+/// `tmxu sessions` so it lists the sessions of the server being targeted
+const SERVER_FLAGS: [&str; 3] = ["socket-name", "socket-path", "tmux-path"];
+
+/// `--long` and `-s` spellings of `SERVER_FLAGS`
+fn server_flag_names() -> Vec<String> {
+    let mut names = Vec::new();
+    for flag in FLAGS.iter().filter(|f| SERVER_FLAGS.contains(&f.long)) {
+        names.push(format!("--{}", flag.long));
+        if let Some(short) = flag.short {
+            names.push(format!("-{short}"));
+        }
+    }
+    names
+}
+
+/// Render the completion script for `shell`
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+fn bash() -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut cases = String::new();
+    for flag in FLAGS {
+        let mut names = vec![format!("--{}", flag.long)];
+        if let Some(short) = flag.short {
+            names.push(format!("-{short}"));
+        }
+        let pattern = names.join("|");
+        words.extend(names);
+        match flag.value {
+            ValueKind::None => {}
+            ValueKind::Text => {
+                let _ = writeln!(cases, "        {pattern}) return ;;");
+            }
+            ValueKind::File => {
+                let _ = writeln!(
+                    cases,
+                    "        {pattern}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;"
+                );
+            }
+            ValueKind::Session => {
+                let _ = writeln!(
+                    cases,
+                    "        {pattern}) COMPREPLY=($(compgen -W \"$(_tmxu_sessions)\" -- \"$cur\")); return ;;"
+                );
+            }
+        }
+    }
+    words.push("completions".into());
+
+    let names = server_flag_names();
+
+    // Bash splits `--flag=value` into three words: flag, `=` and value
+    format!(
+        r#"_tmxu_sessions() {{
+    local -a flags
+    local i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{COMP_WORDS[i]}}" in
+            {names})
+                if [[ "${{COMP_WORDS[i+1]}}" == "=" ]]; then
+                    flags+=("${{COMP_WORDS[i]}}=${{COMP_WORDS[i+2]}}")
+                else
+                    flags+=("${{COMP_WORDS[i]}}" "${{COMP_WORDS[i+1]}}")
+                fi ;;
+        esac
+    done
+    tmxu "${{flags[@]}}" sessions 2>/dev/null
+}}
+
+_tmxu() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+{cases}        completions) COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur")); return ;;
+    esac
+    COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+}}
+complete -F _tmxu tmxu
+"#,
+        names = names.join("|"),
+        words = words.join(" "),
+    )
+}
+
+fn zsh() -> String {
+    let mut specs = String::new();
+    for flag in FLAGS {
+        let action = match flag.value {
+            ValueKind::None => String::new(),
+            ValueKind::Text => ":value: ".to_string(),
+            ValueKind::File => ":path:_files".to_string(),
+            ValueKind::Session => ":session:_tmxu_sessions".to_string(),
+        };
+        let help = flag.help.replace('\'', "'\\''");
+        match flag.short {
+            Some(short) => {
+                let _ = writeln!(
+                    specs,
+                    "    '(-{short} --{long})'{{-{short},--{long}}}'[{help}]{action}' \\",
+                    long = flag.long,
+                );
+            }
+            None => {
+                let _ = writeln!(specs, "    '--{}[{help}]{action}' \\", flag.long);
+            }
+        }
+    }
+
+    let names = server_flag_names();
+    let inline: Vec<String> = names
+        .iter()
+        .filter(|n| n.starts_with("--"))
+        .map(|n| format!("{n}=*"))
+        .collect();
+
+    format!(
+        r#"#compdef tmxu
+
+_tmxu_sessions() {{
+    local -a flags sessions
+    local i
+    for ((i = 2; i < CURRENT; i++)); do
+        case $words[i] in
+            ({names}) flags+=($words[i] $words[i+1]) ;;
+            ({inline}) flags+=($words[i]) ;;
+        esac
+    done
+    sessions=(${{(f)"$(tmxu $flags sessions 2>/dev/null)"}})
+    _describe 'session' sessions
+}}
+
+_arguments \
+{specs}    '1:command:(completions)' \
+    '2:shell:(bash zsh fish)'
+"#,
+        names = names.join("|"),
+        inline = inline.join("|"),
+    )
+}
+
+fn fish() -> String {
+    let names = server_flag_names();
+    let inline: Vec<String> = names
+        .iter()
+        .filter(|n| n.starts_with("--"))
+        .map(|n| format!("'{n}=*'"))
+        .collect();
+    let mut out = format!(
+        r#"function __tmxu_sessions
+    set -l words (commandline -opc)
+    set -l flags
+    for i in (seq 2 (count $words))
+        switch $words[$i]
+            case {names}
+                set -a flags $words[$i] $words[(math $i + 1)]
+            case {inline}
+                set -a flags $words[$i]
+        end
+    end
+    tmxu $flags sessions 2>/dev/null
+end
+complete -c tmxu -f
+"#,
+        names = names.join(" "),
+        inline = inline.join(" "),
+    );
+    for flag in FLAGS {
+        let mut line = format!("complete -c tmxu -l {}", flag.long);
+        if let Some(short) = flag.short {
+            let _ = write!(line, " -s {short}");
+        }
+        match flag.value {
+            ValueKind::None => {}
+            ValueKind::Text => line.push_str(" -x"),
+            ValueKind::File => line.push_str(" -r -F"),
+            ValueKind::Session => {
+                line.push_str(" -x -a '(__tmxu_sessions)'");
+            }
+        }
+        let _ = writeln!(line, " -d '{}'", flag.help.replace('\'', "\\'"));
+        out.push_str(&line);
+    }
+    out.push_str(
+        "complete -c tmxu -n __fish_use_subcommand -a completions -d 'Print a shell completion script'\n\
+         complete -c tmxu -n '__fish_seen_subcommand_from completions' -a 'bash zsh fish'\n",
+    );
+    out
+}
@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Current branch of the git work tree containing `dir`, read straight from
+/// `HEAD` rather than running git. A detached HEAD gives the short commit hash.
+pub fn branch(dir: &Path) -> Option<String> {
+    let git_dir = find_git_dir(dir)?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    parse_head(&head)
+}
+
+/// Walk up from `dir` to the nearest `.git`, following the `gitdir:` file
+/// that linked worktrees and submodules use instead of a directory
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for ancestor in dir.ancestors() {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            // A relative gitdir is relative to the work tree; join keeps absolute ones
+            return Some(ancestor.join(target));
+        }
+    }
+    None
+}
+
+fn parse_head(head: &str) -> Option<String> {
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            let name = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+            Some(name.to_string())
+        }
+        None if head.len() >= 7 && head.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Some(head[..7].to_string())
+        }
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_head() {
+        assert_eq!(
+            parse_head("ref: refs/heads/main\n").as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            parse_head("ref: refs/heads/feature/tree\n").as_deref(),
+            Some("feature/tree")
+        );
+        assert_eq!(
+            parse_head("4f2c9a1e0b7d3c5a6e8f9012345678abcdef0123\n").as_deref(),
+            Some("4f2c9a1")
+        );
+        assert_eq!(parse_head("garbage"), None);
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::ui;
+
+/// Appends log records to a file; the TUI owns the terminal, so there is
+/// nowhere else for them to go
+struct FileLogger {
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only tmxu's own records, not those of its dependencies
+        metadata.target().starts_with("tmxu")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(
+            file,
+            "{} {:<5} {}: {}",
+            ui::unix_now(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+    }
+}
+
+/// Append log records to `path`. The level comes from `RUST_LOG`
+/// (`debug`, or `tmxu=trace` style), defaulting to `debug`.
+pub fn init(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("Failed to open log file {}", path.display()))?;
+    let logger = FileLogger {
+        file: Mutex::new(file),
+    };
+    log::set_logger(Box::leak(Box::new(logger))).map_err(|e| eyre!("{e}"))?;
+    log::set_max_level(level_from_env(std::env::var("RUST_LOG").ok().as_deref()));
+    Ok(())
+}
+
+fn level_from_env(value: Option<&str>) -> LevelFilter {
+    value
+        .and_then(|v| v.rsplit('=').next())
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(LevelFilter::Debug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_from_env() {
+        assert_eq!(level_from_env(None), LevelFilter::Debug);
+        assert_eq!(level_from_env(Some("warn")), LevelFilter::Warn);
+        assert_eq!(level_from_env(Some("tmxu=trace")), LevelFilter::Trace);
+        assert_eq!(level_from_env(Some("nonsense")), LevelFilter::Debug);
+    }
+}
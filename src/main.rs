@@ -1,56 +1,348 @@
 mod app;
+mod cli;
+mod clipboard;
+mod completions;
+mod git;
+mod logging;
+mod store;
+mod template;
 mod tmux;
 mod ui;
 
+use std::io::{self, Write};
 use std::os::unix::process::CommandExt;
-use std::process::Command;
+use std::thread;
 use std::time::Duration;
 
 use color_eyre::eyre::Result;
+use crossterm::cursor::MoveTo;
 use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::queue;
+use crossterm::style::Print;
+use crossterm::terminal::{Clear, ClearType};
 use ratatui::DefaultTerminal;
 
-use crate::app::{Action, App};
+use crate::app::{Action, App, AttachRequest};
+use crate::cli::{Args, WindowSort};
 
 fn main() -> Result<()> {
-    color_eyre::install()?;
+    install_hooks()?;
 
-    let no_logo = std::env::args().any(|a| a == "--no-logo");
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("tmxu: {e}");
+            std::process::exit(2);
+        }
+    };
+    if let Some(shell) = args.completions {
+        print!("{}", completions::script(shell));
+        return Ok(());
+    }
+
+    // Log to --log, or with only RUST_LOG set, to tmxu.log in the data directory
+    let log_path = match args.log.clone() {
+        Some(path) => Some(path),
+        None if std::env::var_os("RUST_LOG").is_some() => Some(store::path("tmxu.log")?),
+        None => None,
+    };
+    if let Some(path) = log_path {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        logging::init(&path)?;
+        log::info!("tmxu {} starting", env!("CARGO_PKG_VERSION"));
+    }
+
+    if let Some(path) = args.tmux_path.clone() {
+        tmux::set_tmux_bin(path);
+    }
+    if let Some(socket) = args.socket.clone() {
+        tmux::set_socket(socket);
+    }
+    tmux::set_dry_run(args.dry_run);
+
+    // For shell completions; with no server there is just nothing to offer
+    if args.sessions {
+        for session in tmux::fetch_sessions().unwrap_or_default() {
+            println!("{}", session.name);
+        }
+        return Ok(());
+    }
+
+    if args.version {
+        println!("tmxu {}", env!("CARGO_PKG_VERSION"));
+        match tmux::version() {
+            Ok(version) => println!("{version}"),
+            Err(e) => println!("tmux: unavailable ({e})"),
+        }
+        return Ok(());
+    }
 
     // Check tmux is available
     if !tmux::is_tmux_available() {
-        eprintln!("tmxu: tmux is not installed or not in PATH");
+        eprintln!(
+            "tmxu: tmux is not installed or not in PATH (tried '{}')",
+            tmux::tmux_bin()
+        );
         std::process::exit(1);
     }
 
+    if args.json {
+        let sessions = tmux::fetch_sessions()?;
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+        return Ok(());
+    }
+
+    if args.watch {
+        return watch(&args);
+    }
+
+    if let Some(ref name) = args.create {
+        if let Err(reason) = tmux::validate_session_name(name) {
+            eprintln!("tmxu: {reason}");
+            std::process::exit(1);
+        }
+        match tmux::create_session(name, None) {
+            Ok(()) => println!("Created session '{name}'"),
+            Err(e) if e.downcast_ref::<tmux::DryRun>().is_some() => {
+                println!("{e}");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("tmxu: {e}");
+                std::process::exit(1);
+            }
+        }
+        if args.attach.is_none() {
+            return Ok(());
+        }
+    }
+
+    if let Some(ref path) = args.load {
+        let result = template::load(path).and_then(|template| {
+            template::build(&template)?;
+            Ok(template.session_name)
+        });
+        let dry_run = tmux::take_dry_run_log();
+        for line in &dry_run {
+            println!("[dry-run] would run: {line}");
+        }
+        match result {
+            Ok(name) if dry_run.is_empty() => println!("Created session '{name}'"),
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                eprintln!("tmxu: {e:#}");
+                std::process::exit(1);
+            }
+        }
+        if args.attach.is_none() {
+            return Ok(());
+        }
+    }
+
+    if let Some(ref name) = args.attach {
+        let sessions = tmux::fetch_sessions()?;
+        if !sessions.iter().any(|s| &s.name == name) {
+            eprintln!("tmxu: no session named '{name}'");
+            std::process::exit(1);
+        }
+        return exec_tmux_attach(&AttachRequest {
+            target: name.clone(),
+            read_only: false,
+            steps: Vec::new(),
+        });
+    }
+
+    if let Some(ref name) = args.ensure {
+        match tmux::ensure_session(name, None) {
+            Ok(_) => {}
+            // Nothing to attach to when the create was only recorded
+            Err(e) if e.downcast_ref::<tmux::DryRun>().is_some() => {
+                println!("{e}");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+        return exec_tmux_attach(&AttachRequest {
+            target: name.clone(),
+            read_only: false,
+            steps: Vec::new(),
+        });
+    }
+
+    if let Some(max_idle) = args.kill_idle {
+        return kill_idle_sessions(max_idle, args.yes);
+    }
+
     let mut terminal = ratatui::init();
     terminal.clear()?;
-    let result = run(&mut terminal, no_logo);
+    let result = run(&mut terminal, &args);
     ratatui::restore();
 
+    for line in tmux::take_dry_run_log() {
+        println!("[dry-run] would run: {line}");
+    }
+
     // If we're attaching, exec into tmux after terminal cleanup
     match result {
-        Ok(Some(target)) => exec_tmux_attach(&target),
+        Ok(Some(request)) => exec_tmux_attach(&request),
         Ok(None) => Ok(()),
         Err(e) => Err(e),
     }
 }
 
-/// Main event loop. Returns Some(target) if user wants to attach, None if quit.
-fn run(terminal: &mut DefaultTerminal, no_logo: bool) -> Result<Option<String>> {
-    let mut app = App::new(no_logo)?;
+/// Kill every session idle for longer than `max_idle`, asking first unless `yes`
+fn kill_idle_sessions(max_idle: Duration, yes: bool) -> Result<()> {
+    let now = ui::unix_now();
+    let idle: Vec<_> = tmux::fetch_sessions()?
+        .into_iter()
+        .filter(|s| s.activity > 0 && s.idle_secs(now) > max_idle.as_secs())
+        .collect();
+
+    if idle.is_empty() {
+        println!(
+            "No sessions idle longer than {}",
+            ui::format_age(max_idle.as_secs())
+        );
+        return Ok(());
+    }
+
+    for session in &idle {
+        println!(
+            "  {}  (idle {})",
+            session.name,
+            ui::format_age(session.idle_secs(now))
+        );
+    }
+    if !yes {
+        print!("Kill {} idle session(s)? [y/N] ", idle.len());
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Aborted");
+            return Ok(());
+        }
+    }
+
+    let mut failed = false;
+    for session in &idle {
+        match tmux::kill_session(&session.name) {
+            Ok(()) => println!("Killed '{}'", session.name),
+            Err(e) if e.downcast_ref::<tmux::DryRun>().is_some() => println!("{e}"),
+            Err(e) => {
+                eprintln!("tmxu: {e}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
+/// Reprint the session tree in place every refresh interval until interrupted.
+/// Only moves and clears relative to the top of the screen, never hides the
+/// cursor or enters raw mode, so Ctrl+C's default exit leaves the terminal as is.
+fn watch(args: &Args) -> Result<()> {
+    let interval = args.refresh_interval.unwrap_or(app::AUTO_REFRESH_INTERVAL);
+    let mut stdout = io::stdout();
     loop {
-        terminal.draw(|frame| ui::draw(frame, &mut app))?;
+        let sessions = tmux::fetch_sessions().map(|mut sessions| {
+            tmux::fill_branches(&mut sessions);
+            if args.window_sort == WindowSort::Activity {
+                tmux::sort_windows_by_activity(&mut sessions);
+            }
+            sessions
+        });
+        let body = match sessions {
+            Ok(sessions) if sessions.is_empty() => "No tmux sessions\n".to_string(),
+            Ok(sessions) => format!(
+                "{}\n\n{}",
+                ui::session_summary(&sessions),
+                ui::render_plain(
+                    &sessions,
+                    args.window_format
+                        .as_deref()
+                        .unwrap_or(tmux::DEFAULT_WINDOW_FORMAT),
+                    args.fish_paths,
+                    args.all_panes,
+                )
+            ),
+            Err(e) => format!("tmxu: {e}\n"),
+        };
+
+        queue!(stdout, MoveTo(0, 0))?;
+        for line in body.lines() {
+            queue!(
+                stdout,
+                Print(line),
+                Clear(ClearType::UntilNewLine),
+                Print("\n")
+            )?;
+        }
+        queue!(stdout, Clear(ClearType::FromCursorDown))?;
+        stdout.flush()?;
+
+        thread::sleep(interval);
+    }
+}
+
+/// Install color-eyre's error and panic hooks. The panic hook restores the
+/// terminal first so a crash mid-draw leaves a usable shell and a readable report.
+fn install_hooks() -> Result<()> {
+    let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default().into_hooks();
+    eyre_hook.install()?;
+
+    let panic_hook = panic_hook.into_panic_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        ratatui::restore();
+        panic_hook(info);
+    }));
+    Ok(())
+}
+
+/// Main event loop. Returns Some(request) if user wants to attach, None if quit.
+fn run(terminal: &mut DefaultTerminal, args: &Args) -> Result<Option<AttachRequest>> {
+    let mut app = App::new(args)?;
+
+    loop {
+        // Only redraw when something visible changed
+        if app.dirty {
+            terminal.draw(|frame| ui::draw(frame, &mut app))?;
+            app.dirty = false;
+        }
 
         // Poll with timeout for tick-based updates (flash message expiry)
         if event::poll(Duration::from_millis(250))? {
-            if let Event::Key(key) = event::read()? {
-                // Only handle key press events (not release/repeat)
+            let mut event = event::read()?;
+
+            // crossterm turns SIGWINCH into Resize events, which wake the
+            // poll at once. A drag delivers a burst; only the last size matters
+            let mut resized = false;
+            while let Event::Resize(_, _) = event {
+                resized = true;
+                if !event::poll(Duration::ZERO)? {
+                    break;
+                }
+                event = event::read()?;
+            }
+            if resized {
+                // Repaint from scratch so no stale cells survive the new layout,
+                // and do it now rather than after the tick's refresh work
+                terminal.clear()?;
+                terminal.draw(|frame| ui::draw(frame, &mut app))?;
+                app.dirty = false;
+            }
+
+            // Only handle key press events (not release/repeat)
+            if let Event::Key(key) = event {
                 if key.kind == KeyEventKind::Press {
                     match app.handle_key_event(key) {
                         Action::Quit => return Ok(None),
-                        Action::Attach(target) => return Ok(Some(target)),
+                        Action::Attach(request) => return Ok(Some(request)),
                         Action::Refresh => app.refresh(),
                         Action::None => {}
                     }
@@ -63,10 +355,22 @@ fn run(terminal: &mut DefaultTerminal, no_logo: bool) -> Result<Option<String>>
 }
 
 /// Replace current process with tmux attach. Never returns on success.
-fn exec_tmux_attach(target: &str) -> Result<()> {
-    let err = Command::new("tmux")
-        .args(["attach-session", "-t", target])
-        .exec();
+/// Inside tmux, nesting a client is refused, so switch this client instead.
+fn exec_tmux_attach(request: &AttachRequest) -> Result<()> {
+    for step in &request.steps {
+        // Landing on the session's current window beats not attaching at all
+        if let Err(e) = step.run() {
+            eprintln!("tmxu: {e}");
+        }
+    }
+
+    // Saved as late as possible, for a session that is still there; a failure
+    // of the attach itself can't be seen once exec() has replaced tmxu
+    if tmux::has_session(request.session()) {
+        app::save_last_attached(request.session());
+    }
+
+    let err = request.command(tmux::inside_tmux()).exec();
 
     // exec() only returns if it fails
     Err(err.into())
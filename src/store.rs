@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::PathBuf;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Where tmxu keeps state between runs: `$XDG_DATA_HOME/tmxu`,
+/// else `~/.local/share/tmxu`
+fn data_dir() -> Result<PathBuf> {
+    match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => Ok(PathBuf::from(dir).join("tmxu")),
+        None => std::env::var_os("HOME")
+            .filter(|d| !d.is_empty())
+            .map(|home| PathBuf::from(home).join(".local/share/tmxu"))
+            .ok_or_else(|| eyre!("Neither XDG_DATA_HOME nor HOME is set")),
+    }
+}
+
+/// Full path of `name` in the data directory
+pub fn path(name: &str) -> Result<PathBuf> {
+    Ok(data_dir()?.join(name))
+}
+
+/// Read the JSON file `name` from the data directory; a missing file is the default value
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let path = path(name)?;
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .wrap_err_with(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Write `value` as the JSON file `name` in the data directory
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir).wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+    let json = serde_json::to_vec_pretty(value)?;
+
+    // Write then rename so a crash mid-write can't truncate the old file
+    let path = dir.join(name);
+    let tmp = dir.join(format!("{name}.tmp"));
+    fs::write(&tmp, json).wrap_err_with(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("tmxu-store-{}", std::process::id()));
+        std::env::set_var("XDG_DATA_HOME", &dir);
+
+        let empty: HashMap<String, String> = load("notes.json").unwrap();
+        assert!(empty.is_empty());
+
+        let notes = HashMap::from([("dev".to_string(), "staging deploy".to_string())]);
+        save("notes.json", &notes).unwrap();
+        assert_eq!(
+            load::<HashMap<String, String>>("notes.json").unwrap(),
+            notes
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,621 @@
+use std::fs;
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Context, Result};
+
+use crate::tmux::{self, TmuxSession};
+
+/// A session layout to build, read from a subset of tmuxp's YAML format:
+///
+/// ```yaml
+/// session_name: api
+/// start_directory: ~/code/api
+/// windows:
+///   - window_name: editor
+///     layout: main-vertical
+///     panes:
+///       - vim
+///       - shell_command:
+///           - cargo watch -x test
+///   - window_name: server
+///     panes:
+///       - cargo run
+/// ```
+///
+/// Other tmuxp options are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub session_name: String,
+    pub start_directory: Option<String>,
+    pub windows: Vec<WindowTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowTemplate {
+    pub name: Option<String>,
+    /// Overrides the session's start directory; relative paths are under it
+    pub start_directory: Option<String>,
+    /// Applied as panes are added, e.g. "tiled" or "main-vertical"
+    pub layout: Option<String>,
+    /// Commands typed into each pane; an empty list leaves just the shell.
+    /// Always at least one pane.
+    pub panes: Vec<Vec<String>>,
+}
+
+/// Read and parse the template at `path`
+pub fn load(path: &Path) -> Result<Template> {
+    let text =
+        fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    parse(&text).wrap_err_with(|| format!("Invalid template {}", path.display()))
+}
+
+/// Create the template's session with its windows and panes, typing each
+/// pane's commands in. Under `--dry-run` every step is recorded rather than
+/// stopping at the first.
+pub fn build(template: &Template) -> Result<()> {
+    let session = template.session_name.as_str();
+    if tmux::has_session(session) {
+        return Err(eyre!("Session '{session}' already exists"));
+    }
+    let current = format!("{session}:");
+
+    for (i, window) in template.windows.iter().enumerate() {
+        let dir = window
+            .start_directory
+            .as_deref()
+            .or(template.start_directory.as_deref());
+        let build_window = || -> Result<()> {
+            if i == 0 {
+                step(tmux::create_session(session, dir))?;
+                if let Some(name) = &window.name {
+                    step(tmux::rename_window(&current, name))?;
+                }
+            } else {
+                step(tmux::new_window(session, window.name.as_deref(), dir))?;
+            }
+            for (p, commands) in window.panes.iter().enumerate() {
+                if p > 0 {
+                    step(tmux::split_window(session, dir))?;
+                    // Re-tile as we go so later splits still have room
+                    if let Some(layout) = &window.layout {
+                        step(tmux::select_layout(session, layout))?;
+                    }
+                }
+                for command in commands {
+                    step(tmux::send_keys(&current, command))?;
+                }
+            }
+            Ok(())
+        };
+        build_window().wrap_err_with(|| match &window.name {
+            Some(name) => format!("Failed to build window '{name}'"),
+            None => format!("Failed to build window {}", i + 1),
+        })?;
+    }
+
+    if template.windows.len() > 1 {
+        step(tmux::select_window(&format!("{session}:^")))?;
+    }
+    Ok(())
+}
+
+/// A step's result, counting one skipped by `--dry-run` as done
+fn step(result: Result<()>) -> Result<()> {
+    match result {
+        Err(e) if e.downcast_ref::<tmux::DryRun>().is_some() => Ok(()),
+        result => result,
+    }
+}
+
+/// Shells whose name says nothing about what a pane was doing
+const SHELLS: [&str; 5] = ["bash", "zsh", "fish", "sh", "dash"];
+
+/// A template that rebuilds `session` as best it can: each window in its
+/// active pane's directory, and each pane re-running its foreground command.
+/// Arguments, layouts and anything typed into a shell are not recoverable.
+pub fn from_session(session: &TmuxSession) -> Template {
+    let shell = tmux::default_shell();
+    let is_shell = |command: &str| SHELLS.contains(&command) || Some(command) == shell.as_deref();
+    let windows = session
+        .windows
+        .iter()
+        .map(|window| WindowTemplate {
+            name: Some(window.name.clone()),
+            start_directory: window
+                .panes
+                .iter()
+                .find(|p| p.active)
+                .or(window.panes.first())
+                .map(|p| p.current_path.clone()),
+            layout: None,
+            panes: window
+                .panes
+                .iter()
+                .map(|pane| match pane.current_command.as_str() {
+                    command if command.is_empty() || is_shell(command) => Vec::new(),
+                    command => vec![command.to_string()],
+                })
+                .collect(),
+        })
+        .collect();
+    Template {
+        session_name: session.name.clone(),
+        start_directory: None,
+        windows,
+    }
+}
+
+/// Render `template` as YAML that `parse` (and tmuxp) reads back
+pub fn to_yaml(template: &Template) -> String {
+    let mut out = format!("session_name: {}\n", yaml_scalar(&template.session_name));
+    if let Some(dir) = &template.start_directory {
+        out.push_str(&format!("start_directory: {}\n", yaml_scalar(dir)));
+    }
+    out.push_str("windows:\n");
+    for window in &template.windows {
+        let mut prefix = "  - ";
+        let mut field = |out: &mut String, key: &str, value: &str| {
+            out.push_str(&format!("{prefix}{key}: {}\n", yaml_scalar(value)));
+            prefix = "    ";
+        };
+        if let Some(name) = &window.name {
+            field(&mut out, "window_name", name);
+        }
+        if let Some(dir) = &window.start_directory {
+            field(&mut out, "start_directory", dir);
+        }
+        if let Some(layout) = &window.layout {
+            field(&mut out, "layout", layout);
+        }
+        out.push_str(&format!("{prefix}panes:\n"));
+        for commands in &window.panes {
+            match commands.as_slice() {
+                [] => out.push_str("      - blank\n"),
+                [command] => out.push_str(&format!("      - {}\n", yaml_scalar(command))),
+                commands => {
+                    out.push_str("      - shell_command:\n");
+                    for command in commands {
+                        out.push_str(&format!("          - {}\n", yaml_scalar(command)));
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `s` as a YAML scalar, single-quoted when plain text would read differently
+fn yaml_scalar(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !matches!(s, "~" | "null" | "blank" | "pane")
+        && !s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with(':');
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "''"))
+    }
+}
+
+/// Parse template YAML
+pub fn parse(text: &str) -> Result<Template> {
+    let lines = text
+        .lines()
+        .enumerate()
+        .filter_map(|(i, raw)| {
+            let text = strip_comment(raw).trim_end();
+            let trimmed = text.trim_start();
+            if trimmed.is_empty() || trimmed == "---" {
+                return None;
+            }
+            Some(Line {
+                number: i + 1,
+                indent: text.len() - trimmed.len(),
+                text: trimmed.to_string(),
+            })
+        })
+        .collect();
+    let mut parser = Parser { lines, pos: 0 };
+    let value = match parser.lines.first() {
+        Some(line) => {
+            let indent = line.indent;
+            parser.block(indent)?
+        }
+        None => Value::Null,
+    };
+    if let Some(line) = parser.lines.get(parser.pos) {
+        return Err(eyre!("line {}: unexpected indentation", line.number));
+    }
+    template_from(value)
+}
+
+fn template_from(value: Value) -> Result<Template> {
+    let Value::Map(entries) = value else {
+        return Err(eyre!("expected session_name and windows at the top level"));
+    };
+    let mut session_name = None;
+    let mut start_directory = None;
+    let mut windows = None;
+    for (key, value) in entries {
+        match key.as_str() {
+            "session_name" => session_name = Some(text(value, "session_name")?),
+            "start_directory" => {
+                start_directory = Some(expand_home(&text(value, "start_directory")?))
+            }
+            "windows" => windows = Some(value),
+            _ => {}
+        }
+    }
+
+    let session_name = session_name.ok_or_else(|| eyre!("missing session_name"))?;
+    tmux::validate_session_name(&session_name).map_err(|reason| eyre!("session_name: {reason}"))?;
+    let windows = match windows {
+        Some(Value::List(windows)) if !windows.is_empty() => windows,
+        _ => return Err(eyre!("windows should list at least one window")),
+    };
+    let windows = windows
+        .into_iter()
+        .enumerate()
+        .map(|(i, window)| {
+            window_from(window, start_directory.as_deref())
+                .wrap_err_with(|| format!("window {}", i + 1))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Template {
+        session_name,
+        start_directory,
+        windows,
+    })
+}
+
+fn window_from(value: Value, session_dir: Option<&str>) -> Result<WindowTemplate> {
+    let Value::Map(entries) = value else {
+        return Err(eyre!("expected window_name, panes, …"));
+    };
+    let mut window = WindowTemplate {
+        name: None,
+        start_directory: None,
+        layout: None,
+        panes: Vec::new(),
+    };
+    for (key, value) in entries {
+        match key.as_str() {
+            "window_name" => window.name = Some(text(value, "window_name")?),
+            "layout" => window.layout = Some(text(value, "layout")?),
+            "start_directory" => {
+                let dir = expand_home(&text(value, "start_directory")?);
+                window.start_directory = Some(match session_dir {
+                    Some(base) if Path::new(&dir).is_relative() => {
+                        Path::new(base).join(dir).to_string_lossy().into_owned()
+                    }
+                    _ => dir,
+                });
+            }
+            "panes" => {
+                let Value::List(panes) = value else {
+                    return Err(eyre!("panes should be a list"));
+                };
+                window.panes = panes.into_iter().map(pane_from).collect::<Result<_>>()?;
+            }
+            _ => {}
+        }
+    }
+    if window.panes.is_empty() {
+        window.panes.push(Vec::new());
+    }
+    Ok(window)
+}
+
+/// A pane's commands: a bare command, `shell_command` with one or a list,
+/// or nothing (`-`, `null`, `blank` or `pane`) for just a shell
+fn pane_from(value: Value) -> Result<Vec<String>> {
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::Str(s) if s == "blank" || s == "pane" => Ok(Vec::new()),
+        Value::Str(command) => Ok(vec![command]),
+        Value::Map(entries) => match entries.into_iter().find(|(k, _)| k == "shell_command") {
+            None | Some((_, Value::Null)) => Ok(Vec::new()),
+            Some((_, Value::Str(command))) => Ok(vec![command]),
+            Some((_, Value::List(commands))) => commands
+                .into_iter()
+                .map(|c| text(c, "shell_command"))
+                .collect(),
+            Some(_) => Err(eyre!("shell_command should be a command or a list")),
+        },
+        Value::List(_) => Err(eyre!("a pane should be a command or shell_command")),
+    }
+}
+
+fn text(value: Value, key: &str) -> Result<String> {
+    match value {
+        Value::Str(s) => Ok(s),
+        _ => Err(eyre!("{key} should be text")),
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    let home = std::env::var("HOME").ok().filter(|h| !h.is_empty());
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{home}{rest}")
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// The YAML shapes a template can hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Null,
+    Str(String),
+    List(Vec<Value>),
+    /// Entries in file order
+    Map(Vec<(String, Value)>),
+}
+
+/// A line with content: 1-based number, indentation and text without comment
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+/// Block-style YAML: nested maps and lists by indentation, with plain,
+/// quoted or `[a, b]` scalars. Anchors, multi-line strings and flow maps
+/// are not supported.
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    fn block(&mut self, indent: usize) -> Result<Value> {
+        if is_item(&self.lines[self.pos].text) {
+            self.list(indent)
+        } else {
+            self.map(indent)
+        }
+    }
+
+    fn list(&mut self, indent: usize) -> Result<Value> {
+        let mut items = Vec::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent != indent || !is_item(&line.text) {
+                break;
+            }
+            let rest = line.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent, false)?);
+            } else if split_key(&rest).is_some() {
+                // "- key: value" opens a map whose keys line up with "key"
+                let line = &mut self.lines[self.pos];
+                line.indent += line.text.len() - rest.len();
+                line.text = rest;
+                let map_indent = line.indent;
+                items.push(self.map(map_indent)?);
+            } else {
+                self.pos += 1;
+                items.push(scalar(&rest));
+            }
+        }
+        Ok(Value::List(items))
+    }
+
+    fn map(&mut self, indent: usize) -> Result<Value> {
+        let mut entries = Vec::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent != indent || is_item(&line.text) {
+                break;
+            }
+            let number = line.number;
+            let (key, rest) = split_key(&line.text)
+                .map(|(key, rest)| (key.to_string(), rest.to_string()))
+                .ok_or_else(|| eyre!("line {number}: expected `key: value`"))?;
+            self.pos += 1;
+            let value = if rest.is_empty() {
+                self.nested(indent, true)?
+            } else {
+                scalar(&rest)
+            };
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    /// The block under a `key:` or `-` with nothing after it, or null if there
+    /// is none. A key's list may sit at the key's own indentation.
+    fn nested(&mut self, indent: usize, key: bool) -> Result<Value> {
+        let next = self
+            .lines
+            .get(self.pos)
+            .map(|line| (line.indent, is_item(&line.text)));
+        match next {
+            Some((deeper, _)) if deeper > indent => self.block(deeper),
+            Some((same, true)) if key && same == indent => self.list(indent),
+            _ => Ok(Value::Null),
+        }
+    }
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Split `key: value` (or `key:` with the value on following lines). Keys
+/// are single words, so a command like `echo a: b` stays a plain string.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let (key, rest) = match text.split_once(": ") {
+        Some((key, rest)) => (key, rest.trim()),
+        None => (text.strip_suffix(':')?, ""),
+    };
+    let plain =
+        !key.is_empty() && !key.starts_with(['"', '\'', '[']) && !key.contains(char::is_whitespace);
+    plain.then_some((key, rest))
+}
+
+fn scalar(s: &str) -> Value {
+    let quoted = |q: char| s.len() >= 2 && s.starts_with(q) && s.ends_with(q);
+    if s == "~" || s == "null" {
+        Value::Null
+    } else if quoted('"') {
+        Value::Str(
+            s[1..s.len() - 1]
+                .replace("\\\"", "\"")
+                .replace("\\\\", "\\"),
+        )
+    } else if quoted('\'') {
+        Value::Str(s[1..s.len() - 1].replace("''", "'"))
+    } else if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim();
+        if inner.is_empty() {
+            Value::List(Vec::new())
+        } else {
+            Value::List(inner.split(',').map(|item| scalar(item.trim())).collect())
+        }
+    } else {
+        Value::Str(s.to_string())
+    }
+}
+
+/// `raw` up to any `#` comment. A `#` only starts one after whitespace and
+/// outside quotes, and quotes only open at the start of a word.
+fn strip_comment(raw: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in raw.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && (prev.is_whitespace() || "[,:-".contains(prev)) => {
+                quote = Some(c)
+            }
+            None if c == '#' && prev.is_whitespace() => return &raw[..i],
+            None => {}
+        }
+        prev = c;
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        let yaml = "\
+# dev layout
+session_name: api
+start_directory: /srv/api
+windows:
+- window_name: editor
+  layout: main-vertical   # editor on the left
+  panes:
+    - vim
+    - shell_command:
+        - cd tests
+        - cargo watch -x test
+    -
+- window_name: 'logs #1'
+  start_directory: log
+  panes:
+    - shell_command: \"tail -f app.log\"
+- panes: [blank, 'git status']
+";
+        let template = parse(yaml).unwrap();
+        assert_eq!(template.session_name, "api");
+        assert_eq!(template.start_directory.as_deref(), Some("/srv/api"));
+        assert_eq!(
+            template.windows,
+            vec![
+                WindowTemplate {
+                    name: Some("editor".into()),
+                    start_directory: None,
+                    layout: Some("main-vertical".into()),
+                    panes: vec![
+                        vec!["vim".into()],
+                        vec!["cd tests".into(), "cargo watch -x test".into()],
+                        vec![],
+                    ],
+                },
+                WindowTemplate {
+                    name: Some("logs #1".into()),
+                    start_directory: Some("/srv/api/log".into()),
+                    layout: None,
+                    panes: vec![vec!["tail -f app.log".into()]],
+                },
+                WindowTemplate {
+                    name: None,
+                    start_directory: None,
+                    layout: None,
+                    panes: vec![vec![], vec!["git status".into()]],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let template = Template {
+            session_name: "proj/api".into(),
+            start_directory: None,
+            windows: vec![
+                WindowTemplate {
+                    name: Some("edit: main".into()),
+                    start_directory: Some("/home/user/it's here".into()),
+                    layout: None,
+                    panes: vec![vec!["vim".into()], vec![]],
+                },
+                WindowTemplate {
+                    name: Some("-".into()),
+                    start_directory: None,
+                    layout: Some("tiled".into()),
+                    panes: vec![vec!["make".into(), "#1 null".into()]],
+                },
+            ],
+        };
+        assert_eq!(parse(&to_yaml(&template)).unwrap(), template);
+    }
+
+    #[test]
+    fn test_from_session() {
+        let output = "dev|$0|1|2|1700000000|0|edit|1|0|vim|/home/user/app|0\n\
+                       dev|$0|1|2|1700000000|0|edit|1|1|zsh|/home/user/app/src|1\n\
+                       dev|$0|1|2|1700000000|1|logs|0|0|tail|/var/log|1\n";
+        let sessions = tmux::parse_sessions(output).unwrap();
+        let template = from_session(&sessions[0]);
+
+        assert_eq!(template.session_name, "dev");
+        assert_eq!(
+            template.windows[0].start_directory.as_deref(),
+            Some("/home/user/app/src")
+        );
+        assert_eq!(
+            template.windows[0].panes,
+            vec![vec!["vim".to_string()], vec![]]
+        );
+        assert_eq!(template.windows[1].name.as_deref(), Some("logs"));
+        assert_eq!(template.windows[1].panes, vec![vec!["tail".to_string()]]);
+    }
+
+    #[test]
+    fn test_parse_template_errors() {
+        assert!(parse("windows:\n  - window_name: a\n").is_err());
+        assert!(parse("session_name: a\n").is_err());
+        assert!(parse("session_name: a\nwindows: []\n").is_err());
+        assert!(parse("session_name: a.b\nwindows:\n  - panes: [vim]\n").is_err());
+        // A pane command that merely contains ": " is not a map
+        let template = parse("session_name: a\nwindows:\n  - panes:\n    - echo a: b\n").unwrap();
+        assert_eq!(
+            template.windows[0].panes,
+            vec![vec!["echo a: b".to_string()]]
+        );
+        // Indentation that fits nowhere
+        assert!(parse("session_name: a\n    stray: 1\nwindows:\n  - panes: [vim]\n").is_err());
+    }
+}
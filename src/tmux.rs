@@ -1,25 +1,220 @@
-use std::process::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use color_eyre::eyre::{eyre, Context, Result};
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+use crate::git;
+
+/// Which tmux server to talk to, mirroring tmux's own `-L` / `-S` flags
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Socket {
+    /// Named socket in the default socket directory (`-L`)
+    Name(String),
+    /// Full path to a socket (`-S`)
+    Path(String),
+}
+
+static SOCKET: OnceLock<Socket> = OnceLock::new();
+static TMUX_BIN: OnceLock<String> = OnceLock::new();
+static VERSION: OnceLock<Option<Version>> = OnceLock::new();
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static DRY_RUN_LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Last CPU time sample per process, for `fill_usage` to diff against
+static CPU_SAMPLES: Mutex<BTreeMap<u32, (u64, Instant)>> = Mutex::new(BTreeMap::new());
+
+/// A tmux release as (major, minor); letter suffixes like the "a" in 3.3a are dropped
+pub type Version = (u32, u32);
+
+/// Oldest tmux that knows every format variable in `FULL_FORMAT`
+pub const MIN_VERSION: Version = (2, 1);
+
+/// Override the tmux binary used for every invocation.
+/// Must be called before the first tmux command; later calls are ignored.
+pub fn set_tmux_bin(path: String) {
+    let _ = TMUX_BIN.set(path);
+}
+
+/// The tmux binary: `--tmux-path`, else `$TMXU_TMUX_BIN`, else `tmux` from PATH
+pub fn tmux_bin() -> &'static str {
+    TMUX_BIN.get_or_init(|| std::env::var("TMXU_TMUX_BIN").unwrap_or_else(|_| "tmux".to_string()))
+}
+
+/// Select the server socket used by every subsequent tmux invocation.
+/// Must be called before the first tmux command; later calls are ignored.
+pub fn set_socket(socket: Socket) {
+    let _ = SOCKET.set(socket);
+}
+
+/// Make every state-changing tmux command a no-op that is only recorded.
+/// Read-only commands such as `fetch_sessions` still run.
+pub fn set_dry_run(on: bool) {
+    DRY_RUN.store(on, Ordering::Relaxed);
+}
+
+/// Commands skipped under `--dry-run`, oldest first, emptying the record
+pub fn take_dry_run_log() -> Vec<String> {
+    std::mem::take(&mut *DRY_RUN_LOG.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Returned by state-changing tmux calls under `--dry-run` instead of running them
+#[derive(Debug)]
+pub struct DryRun(pub String);
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[dry-run] would run: {}", self.0)
+    }
+}
+
+impl std::error::Error for DryRun {}
+
+/// Run a tmux command that changes server state, failing with its stderr.
+/// Under `--dry-run` it is recorded and fails with `DryRun` instead.
+fn run_mutation(args: &[&str], what: &str) -> Result<()> {
+    let mut cmd = tmux_command();
+    cmd.args(args);
+    if DRY_RUN.load(Ordering::Relaxed) {
+        let line = command_line(&cmd);
+        DRY_RUN_LOG
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(line.clone());
+        return Err(DryRun(line).into());
+    }
+
+    let output = output(&mut cmd).wrap_err_with(|| format!("Failed to {what}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("Failed to {what}: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+/// Run `cmd` to completion, logging its command line, exit status and stderr
+fn output(cmd: &mut Command) -> std::io::Result<Output> {
+    let line = command_line(cmd);
+    let started = Instant::now();
+    let result = cmd.output();
+    match &result {
+        Ok(out) => {
+            log::debug!("{line} -> {} in {:?}", out.status, started.elapsed());
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            if !stderr.trim().is_empty() {
+                log::debug!("stderr: {}", stderr.trim());
+            }
+        }
+        Err(e) => log::warn!("{line} -> failed to run: {e}"),
+    }
+    result
+}
+
+/// `cmd` as it could be typed into a shell
+pub fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Build a `tmux` command aimed at the configured server
+pub fn tmux_command() -> Command {
+    let mut cmd = Command::new(tmux_bin());
+    match SOCKET.get() {
+        Some(Socket::Name(name)) => {
+            cmd.args(["-L", name]);
+        }
+        Some(Socket::Path(path)) => {
+            cmd.args(["-S", path]);
+        }
+        None => {}
+    }
+    cmd
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct TmuxPane {
     pub index: u32,
     pub current_command: String,
     pub current_path: String,
     pub active: bool,
+    pub pid: u32,
+    /// The pane's process has exited and the pane was kept (`remain-on-exit`)
+    pub dead: bool,
+    /// Pane title, empty when it is still tmux's default (the hostname)
+    pub title: String,
+    /// Full command line of the foreground process, filled in on demand
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_line: Option<String>,
+    /// Git branch of `current_path`, filled in by `fill_branches`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// CPU and memory of the foreground process, filled in by `fill_usage`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ProcessUsage>,
+}
+
+/// Resource use of a pane's foreground process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProcessUsage {
+    /// CPU use since the previous sample in tenths of a percent, None on the first
+    pub cpu_tenths: Option<u32>,
+    /// Resident set size in KiB
+    pub rss_kb: u64,
+}
+
+impl TmuxPane {
+    /// The foreground command cleaned up for display (see `normalize_name`)
+    pub fn display_command(&self) -> String {
+        normalize_name(&self.current_command)
+    }
+
+    /// The pane title, if it says something the command doesn't
+    pub fn display_title(&self) -> Option<&str> {
+        if self.title.is_empty() || self.title == self.current_command {
+            None
+        } else {
+            Some(&self.title)
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 #[allow(dead_code)]
 pub struct TmuxWindow {
     pub index: u32,
     pub name: String,
     pub active: bool,
+    /// `synchronize-panes` is on: input goes to every pane
+    pub synchronized: bool,
+    /// Unix time of the last activity in the window
+    pub activity: u64,
+    /// The window is linked into more than one session (`link-window`)
+    pub linked: bool,
     pub panes: Vec<TmuxPane>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 #[allow(dead_code)]
 pub struct TmuxSession {
     pub name: String,
@@ -27,81 +222,287 @@ pub struct TmuxSession {
     pub attached: bool,
     pub window_count: u32,
     pub created: u64,
+    /// Unix time of the last activity in the session
+    pub activity: u64,
     pub windows: Vec<TmuxWindow>,
 }
 
-impl TmuxSession {
-    /// Short display path for a window's active pane
-    pub fn window_summary(window: &TmuxWindow) -> String {
-        let pane = window
+impl TmuxWindow {
+    /// The active pane, falling back to the first
+    pub fn active_pane(&self) -> Option<&TmuxPane> {
+        self.panes.iter().find(|p| p.active).or(self.panes.first())
+    }
+
+    /// The window name cleaned up for display; `name` stays exact for targeting
+    pub fn display_name(&self) -> String {
+        normalize_name(&self.name)
+    }
+
+    /// `display_name`, except that a window still named after `shell` (tmux's
+    /// default name) shows what its active pane is running instead
+    pub fn label(&self, shell: Option<&str>) -> String {
+        let running = self
             .panes
             .iter()
             .find(|p| p.active)
-            .or(window.panes.first());
+            .map(|p| p.current_command.as_str())
+            .filter(|command| !command.is_empty() && Some(*command) != shell);
+        match running {
+            Some(command) if Some(self.name.as_str()) == shell => normalize_name(command),
+            _ => self.display_name(),
+        }
+    }
+}
 
-        match pane {
-            Some(p) => {
-                let path = shorten_path(&p.current_path);
-                format!("{}  {}", p.current_command, path)
+/// Basename of the user's login shell from `$SHELL`, e.g. "zsh"
+pub fn default_shell() -> Option<String> {
+    let shell = std::env::var("SHELL").ok()?;
+    let name = Path::new(&shell).file_name()?.to_str()?;
+    Some(name.to_string())
+}
+
+/// Strip control characters and collapse whitespace runs to one space, so
+/// names set by programs with stray escapes or padding display cleanly
+pub fn normalize_name(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl TmuxSession {
+    /// Seconds since the last activity in this session, relative to `now` (Unix time)
+    pub fn idle_secs(&self, now: u64) -> u64 {
+        now.saturating_sub(self.activity)
+    }
+
+    /// Panes across all of this session's windows
+    pub fn pane_count(&self) -> usize {
+        self.windows.iter().map(|w| w.panes.len()).sum()
+    }
+
+    /// Summary of a window's active pane, rendered from `template`
+    /// (see `DEFAULT_WINDOW_FORMAT` for the placeholders)
+    pub fn window_summary(window: &TmuxWindow, template: &str, fish_paths: bool) -> String {
+        let Some(p) = window.active_pane() else {
+            return String::new();
+        };
+        let path = if fish_paths {
+            shorten_path_fish(&p.current_path)
+        } else {
+            shorten_path(&p.current_path)
+        };
+        let branch = p
+            .branch
+            .as_ref()
+            .map(|b| format!("({b})"))
+            .unwrap_or_default();
+        let panes = window.panes.len().to_string();
+        let command = p.display_command();
+        let summary = fill_template(
+            template,
+            &[
+                ("command", command.as_str()),
+                ("path", path.as_str()),
+                ("panes", panes.as_str()),
+                ("branch", branch.as_str()),
+            ],
+        );
+        // An empty trailing placeholder (no branch) shouldn't leave padding behind
+        summary.trim_end().to_string()
+    }
+}
+
+/// Default `--window-format`. `{command}` and `{path}` come from the active pane,
+/// `{panes}` is the pane count and `{branch}` is "(name)" inside a git repo, else empty.
+pub const DEFAULT_WINDOW_FORMAT: &str = "{command}  {path}  {branch}";
+
+/// Replace each `{name}` in `template` with its value from `fields`.
+/// Unknown placeholders and stray braces are kept as written.
+fn fill_template(template: &str, fields: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let field = tail.find('}').and_then(|end| {
+            let name = &tail[1..end];
+            fields
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (end, *value))
+        });
+        match field {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
             }
-            None => String::new(),
         }
     }
+    out.push_str(rest);
+    out
+}
+
+/// Group prefix of a session name: the part before the first ':' or '/'.
+/// tmux rewrites ':' in session names to '_', so '/' is the practical separator.
+pub fn session_group(name: &str) -> Option<&str> {
+    match name.split_once([':', '/']) {
+        Some((prefix, rest)) if !prefix.is_empty() && !rest.is_empty() => Some(prefix),
+        _ => None,
+    }
 }
 
 /// Shorten home directory to ~ in paths
 pub fn shorten_path(path: &str) -> String {
-    if let Ok(home) = std::env::var("HOME") {
-        if let Some(rest) = path.strip_prefix(&home) {
-            return format!("~{rest}");
-        }
+    shorten_path_with_home(path, std::env::var("HOME").ok().as_deref())
+}
+
+fn shorten_path_with_home(path: &str, home: Option<&str>) -> String {
+    // An unset, empty or root HOME has nothing worth abbreviating
+    let home = home.unwrap_or_default().trim_end_matches('/');
+    if home.is_empty() {
+        return path.to_string();
+    }
+    match path.strip_prefix(home) {
+        // Only on a directory boundary: HOME=/home/u must not touch /home/user
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{rest}"),
+        _ => path.to_string(),
     }
-    path.to_string()
+}
+
+/// Fish-style shortening: home to ~, then every directory but the last cut
+/// to its first character (two for dot-directories), e.g. `~/c/p/tmxu`
+pub fn shorten_path_fish(path: &str) -> String {
+    let path = shorten_path(path);
+    let segments: Vec<&str> = path.split('/').collect();
+    let last = segments.len().saturating_sub(1);
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            if i == last || *segment == "~" {
+                return segment.to_string();
+            }
+            let take = if segment.starts_with('.') { 2 } else { 1 };
+            segment.chars().take(take).collect()
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Parse `tmux -V` output such as "tmux 3.3a" or "tmux next-3.5"
+pub fn parse_version(line: &str) -> Option<Version> {
+    let number = line.split_whitespace().last()?;
+    let number = number.strip_prefix("next-").unwrap_or(number);
+    let (major, minor) = number.split_once('.')?;
+    let digits = minor
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(minor.len());
+    Some((major.parse().ok()?, minor[..digits].parse().ok()?))
+}
+
+/// The server's tmux version, asked once. None when it can't be parsed
+/// (e.g. "tmux master"), which is treated as current.
+pub fn detected_version() -> Option<Version> {
+    *VERSION.get_or_init(|| version().ok().and_then(|v| parse_version(&v)))
+}
+
+/// Whether tmux is older than `MIN_VERSION`, so only `BASIC_FORMAT` is safe
+pub fn is_outdated() -> bool {
+    detected_version().is_some_and(|v| v < MIN_VERSION)
+}
+
+/// Whether tmxu itself is running inside a tmux client
+pub fn inside_tmux() -> bool {
+    std::env::var_os("TMUX").is_some_and(|v| !v.is_empty())
+}
+
+/// The version line tmux reports, e.g. "tmux 3.4"
+pub fn version() -> Result<String> {
+    let output = output(tmux_command().arg("-V")).wrap_err("Failed to run tmux -V")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("Failed to get tmux version: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
 pub fn is_tmux_available() -> bool {
-    Command::new("tmux").arg("list-sessions").output().is_ok()
+    output(tmux_command().arg("list-sessions")).is_ok()
 }
 
-#[allow(dead_code)]
 pub fn is_tmux_server_running() -> bool {
-    Command::new("tmux")
-        .arg("list-sessions")
-        .output()
-        .is_ok_and(|o| o.status.success())
+    output(tmux_command().arg("list-sessions")).is_ok_and(|o| o.status.success())
 }
 
+/// Fields every supported tmux understands; `parse_sessions` needs at least these
+const BASIC_FORMAT: &str = "#{session_name}|#{session_id}|#{session_attached}|#{session_windows}|#{session_created}|#{window_index}|#{window_name}|#{window_active}|#{pane_index}|#{pane_current_command}|#{pane_current_path}|#{pane_active}";
+
+/// `BASIC_FORMAT` plus the optional extras. pane_title is last so a '|'
+/// inside it survives the split.
+const FULL_FORMAT: &str = "#{session_name}|#{session_id}|#{session_attached}|#{session_windows}|#{session_created}|#{window_index}|#{window_name}|#{window_active}|#{pane_index}|#{pane_current_command}|#{pane_current_path}|#{pane_active}|#{pane_pid}|#{host}|#{session_activity}|#{pane_synchronized}|#{pane_dead}|#{window_activity}|#{window_linked}|#{pane_title}";
+
+/// Tries at `list-panes` before its error is reported
+const FETCH_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry; each later retry waits one step longer
+const FETCH_RETRY_DELAY: Duration = Duration::from_millis(50);
+
 /// Fetch all sessions, windows, and panes in a single tmux call.
 pub fn fetch_sessions() -> Result<Vec<TmuxSession>> {
-    let format = "#{session_name}|#{session_id}|#{session_attached}|#{session_windows}|#{session_created}|#{window_index}|#{window_name}|#{window_active}|#{pane_index}|#{pane_current_command}|#{pane_current_path}|#{pane_active}";
+    // Old tmux prints unknown variables literally, so don't ask it for them
+    let format = if is_outdated() {
+        BASIC_FORMAT
+    } else {
+        FULL_FORMAT
+    };
 
-    let output = Command::new("tmux")
-        .args(["list-panes", "-aF", format])
-        .output()
-        .wrap_err("Failed to run tmux list-panes")?;
+    let mut attempt = 1;
+    let output = loop {
+        let output = output(tmux_command().args(["list-panes", "-aF", format]))
+            .wrap_err("Failed to run tmux list-panes")?;
+        if output.status.success() {
+            break output;
+        }
 
-    if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        // "no server running" or "no sessions" are not hard errors
-        if stderr.contains("no server running") || stderr.contains("no sessions") {
+        // A missing server or an empty one is not a hard error
+        if stderr.contains("no server running")
+            || stderr.contains("error connecting to")
+            || stderr.contains("no sessions")
+        {
             return Ok(Vec::new());
         }
-        return Err(eyre!("tmux error: {}", stderr.trim()));
-    }
+        // Anything else may be a server that is still starting up
+        if attempt == FETCH_ATTEMPTS {
+            return Err(eyre!("tmux error: {}", stderr.trim()));
+        }
+        log::debug!("list-panes attempt {attempt} failed, retrying");
+        std::thread::sleep(FETCH_RETRY_DELAY * attempt);
+        attempt += 1;
+    };
 
     let stdout = String::from_utf8_lossy(&output.stdout);
     parse_sessions(&stdout)
 }
 
-fn parse_sessions(output: &str) -> Result<Vec<TmuxSession>> {
-    use std::collections::BTreeMap;
-
+/// Sessions from `list-panes -a -F FULL_FORMAT` output, one line per pane
+pub fn parse_sessions(output: &str) -> Result<Vec<TmuxSession>> {
     // Group by session name, then by window index
     let mut session_map: BTreeMap<String, TmuxSession> = BTreeMap::new();
 
     for line in output.lines() {
-        let parts: Vec<&str> = line.split('|').collect();
+        let parts: Vec<&str> = line.splitn(20, '|').collect();
         if parts.len() < 12 {
+            log::warn!(
+                "skipping list-panes line with {} fields: {line:?}",
+                parts.len()
+            );
             continue;
         }
 
@@ -117,12 +518,30 @@ fn parse_sessions(output: &str) -> Result<Vec<TmuxSession>> {
         let pane_current_command = parts[9].to_string();
         let pane_current_path = parts[10].to_string();
         let pane_active = parts[11].trim() != "0";
+        let pane_pid: u32 = parts.get(12).and_then(|p| p.parse().ok()).unwrap_or(0);
+        let host = parts.get(13).copied().unwrap_or_default();
+        let session_activity: u64 = parts.get(14).and_then(|p| p.parse().ok()).unwrap_or(0);
+        let window_synchronized = parts.get(15).is_some_and(|p| *p == "1");
+        let pane_dead = parts.get(16).is_some_and(|p| *p == "1");
+        let window_activity: u64 = parts.get(17).and_then(|p| p.parse().ok()).unwrap_or(0);
+        let window_linked = parts.get(18).is_some_and(|p| *p == "1");
+        let pane_title = match parts.get(19) {
+            // tmux titles new panes with the hostname; treat that as untitled
+            Some(&title) if title != host => title.to_string(),
+            _ => String::new(),
+        };
 
         let pane = TmuxPane {
             index: pane_index,
             current_command: pane_current_command,
             current_path: pane_current_path,
             active: pane_active,
+            pid: pane_pid,
+            dead: pane_dead,
+            title: pane_title,
+            command_line: None,
+            branch: None,
+            usage: None,
         };
 
         let session = session_map
@@ -133,17 +552,30 @@ fn parse_sessions(output: &str) -> Result<Vec<TmuxSession>> {
                 attached: session_attached,
                 window_count: session_windows,
                 created: session_created,
+                activity: session_activity,
                 windows: Vec::new(),
             });
 
         // Find or create window
         if let Some(window) = session.windows.iter_mut().find(|w| w.index == window_index) {
+            // Pane indices are tree node ids, which must be unique within the
+            // window or the whole tree fails to build
+            if window.panes.iter().any(|p| p.index == pane.index) {
+                log::warn!(
+                    "skipping duplicate pane {}:{window_index}.{pane_index}",
+                    parts[0]
+                );
+                continue;
+            }
             window.panes.push(pane);
         } else {
             session.windows.push(TmuxWindow {
                 index: window_index,
                 name: window_name,
                 active: window_active,
+                synchronized: window_synchronized,
+                activity: window_activity,
+                linked: window_linked,
                 panes: vec![pane],
             });
         }
@@ -158,52 +590,659 @@ fn parse_sessions(output: &str) -> Result<Vec<TmuxSession>> {
         }
     }
 
+    log::debug!(
+        "parsed {} sessions from {} lines",
+        sessions.len(),
+        output.lines().count()
+    );
     Ok(sessions)
 }
 
-pub fn create_session(name: &str) -> Result<()> {
-    let output = Command::new("tmux")
-        .args(["new-session", "-d", "-s", name])
-        .output()
-        .wrap_err("Failed to create tmux session")?;
+/// Resolve tree path `path` (session name, window index, pane index) against
+/// `sessions`, degrading sensibly when part of it is gone: a missing window
+/// becomes the session's active window, and a missing pane, or any pane of a
+/// replacement window, the window's active pane. Panes only appear where the
+/// tree lists them, in multi-pane windows or with `all_panes`.
+/// None when the session itself is gone.
+pub fn resolve_path(
+    sessions: &[&TmuxSession],
+    path: &[String],
+    all_panes: bool,
+) -> Option<Vec<String>> {
+    let (name, rest) = path.split_first()?;
+    let session = sessions.iter().find(|s| &s.name == name)?;
+    let mut resolved = vec![session.name.clone()];
+    let Some(window_id) = rest.first() else {
+        return Some(resolved);
+    };
+
+    let exact = session
+        .windows
+        .iter()
+        .find(|w| w.index.to_string() == *window_id);
+    let Some(window) = exact
+        .or_else(|| session.windows.iter().find(|w| w.active))
+        .or_else(|| session.windows.first())
+    else {
+        return Some(resolved);
+    };
+    resolved.push(window.index.to_string());
+    if window.panes.len() < 2 && !all_panes {
+        return Some(resolved);
+    }
+
+    let pane = match rest.get(1) {
+        Some(pane_id) if exact.is_some() => window
+            .panes
+            .iter()
+            .find(|p| p.index.to_string() == *pane_id),
+        Some(_) => None,
+        // The window row itself was selected
+        None => return Some(resolved),
+    };
+    if let Some(pane) = pane
+        .or_else(|| window.panes.iter().find(|p| p.active))
+        .or_else(|| window.panes.first())
+    {
+        resolved.push(pane.index.to_string());
+    }
+    Some(resolved)
+}
+
+/// Order each session's windows most recently active first; ties keep index order
+pub fn sort_windows_by_activity(sessions: &mut [TmuxSession]) {
+    for session in sessions {
+        session
+            .windows
+            .sort_by_key(|w| std::cmp::Reverse(w.activity));
+    }
+}
 
+/// Which part of a pane a `PaneMatch` scored best on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchField {
+    Session,
+    Command,
+    Path,
+}
+
+/// A pane found by `search_panes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneMatch {
+    pub session: String,
+    pub window: u32,
+    pub pane: u32,
+    pub command: String,
+    pub path: String,
+    /// From `fuzzy_match`; 0 for an empty query
+    pub score: i64,
+    /// None for an empty query
+    pub field: Option<MatchField>,
+}
+
+/// Characters after which a match starts a new word
+const WORD_SEPARATORS: &[char] = &['/', '-', '_', ' ', '.', ':'];
+
+/// Score `query` as a case-insensitive subsequence of `text`, fzf-style:
+/// characters that follow the previous match or start a word score higher,
+/// and gaps between matches cost. Returns the score and the char positions
+/// matched, or None if `query` isn't a subsequence.
+pub fn fuzzy_match(text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let original: Vec<char> = text.chars().collect();
+    let haystack: Vec<char> = original.iter().map(|&c| lower(c)).collect();
+    let needle: Vec<char> = query.chars().map(lower).collect();
+    let Some(&first) = needle.first() else {
+        return Some((0, Vec::new()));
+    };
+
+    let starts_word = |i: usize| {
+        i == 0
+            || WORD_SEPARATORS.contains(&original[i - 1])
+            || (original[i - 1].is_lowercase() && original[i].is_uppercase())
+    };
+
+    // Greedy from every place the first character occurs; keep the best
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    for start in (0..haystack.len()).filter(|&i| haystack[i] == first) {
+        let mut positions = vec![start];
+        for &c in &needle[1..] {
+            let from = positions[positions.len() - 1] + 1;
+            match haystack[from..].iter().position(|&h| h == c) {
+                Some(offset) => positions.push(from + offset),
+                None => break,
+            }
+        }
+        if positions.len() < needle.len() {
+            // Later starts only have less text left to match in
+            break;
+        }
+
+        let mut score = 0;
+        for (n, &i) in positions.iter().enumerate() {
+            score += 16;
+            if starts_word(i) {
+                score += 8;
+            }
+            if n > 0 {
+                let gap = (i - positions[n - 1] - 1) as i64;
+                score += if gap == 0 { 8 } else { -gap.min(8) };
+            }
+        }
+        if best.as_ref().is_none_or(|(top, _)| score > *top) {
+            best = Some((score, positions));
+        }
+    }
+    best
+}
+
+/// Panes whose session name, command or path fuzzy-match `query`, best
+/// first; equal scores keep tree order. An empty query matches every pane.
+pub fn search_panes(sessions: &[TmuxSession], query: &str) -> Vec<PaneMatch> {
+    let mut matches = Vec::new();
+    for session in sessions {
+        for window in &session.windows {
+            for pane in &window.panes {
+                let fields = [
+                    (MatchField::Session, session.name.as_str()),
+                    (MatchField::Command, pane.current_command.as_str()),
+                    (MatchField::Path, pane.current_path.as_str()),
+                ];
+                // The first field wins a tie, so a name match shows as one
+                let mut hit: Option<(i64, MatchField)> = None;
+                for (field, text) in fields {
+                    if let Some((score, _)) = fuzzy_match(text, query) {
+                        if hit.is_none_or(|(top, _)| score > top) {
+                            hit = Some((score, field));
+                        }
+                    }
+                }
+                let Some((score, field)) = hit else {
+                    continue;
+                };
+                matches.push(PaneMatch {
+                    session: session.name.clone(),
+                    window: window.index,
+                    pane: pane.index,
+                    command: pane.current_command.clone(),
+                    path: pane.current_path.clone(),
+                    score,
+                    field: (!query.is_empty()).then_some(field),
+                });
+            }
+        }
+    }
+    // Stable, so ties stay in tree order
+    matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+    matches
+}
+
+/// Fill in each pane's `command_line` from the foreground process under its shell
+pub fn fill_command_lines(sessions: &mut [TmuxSession]) {
+    let Some(table) = process_table() else {
+        return;
+    };
+    for pane in sessions
+        .iter_mut()
+        .flat_map(|s| s.windows.iter_mut())
+        .flat_map(|w| w.panes.iter_mut())
+    {
+        pane.command_line = foreground_process(&table, pane.pid).map(|row| row.2.clone());
+    }
+}
+
+/// `/proc` reports CPU time in USER_HZ ticks, which Linux fixes at 100
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+/// Fill in each pane's `usage` from `/proc` for its foreground process.
+/// CPU is measured since the previous call, so the first one has memory only.
+/// Without `/proc` (macOS) panes are left without usage.
+pub fn fill_usage(sessions: &mut [TmuxSession]) {
+    let Some(table) = process_table() else {
+        return;
+    };
+    let now = Instant::now();
+    let mut samples = CPU_SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut current = BTreeMap::new();
+    for pane in sessions
+        .iter_mut()
+        .flat_map(|s| s.windows.iter_mut())
+        .flat_map(|w| w.panes.iter_mut())
+    {
+        let Some(pid) = foreground_process(&table, pane.pid).map(|row| row.0) else {
+            continue;
+        };
+        let Some(ticks) = fs::read_to_string(format!("/proc/{pid}/stat"))
+            .ok()
+            .and_then(|stat| parse_stat_ticks(&stat))
+        else {
+            continue;
+        };
+        let rss_kb = fs::read_to_string(format!("/proc/{pid}/status"))
+            .ok()
+            .and_then(|status| parse_status_rss(&status))
+            .unwrap_or(0);
+        let cpu_tenths = samples
+            .get(&pid)
+            .and_then(|&(before, at)| cpu_tenths(ticks.saturating_sub(before), now - at));
+        pane.usage = Some(ProcessUsage { cpu_tenths, rss_kb });
+        current.insert(pid, (ticks, now));
+    }
+    // Drop samples of processes that have gone away
+    *samples = current;
+}
+
+/// User plus system CPU ticks from a `/proc/<pid>/stat` line. The command
+/// name is parenthesized and may hold spaces, so count fields after the last ')'.
+fn parse_stat_ticks(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace().skip(11); // utime is field 14
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// `VmRSS` in KiB from `/proc/<pid>/status`
+fn parse_status_rss(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.split_whitespace().next()?.parse().ok())
+}
+
+/// CPU use in tenths of a percent for `ticks` spent over `elapsed`
+fn cpu_tenths(ticks: u64, elapsed: Duration) -> Option<u32> {
+    let millis = elapsed.as_millis();
+    if millis == 0 {
+        return None;
+    }
+    let tenths = u128::from(ticks) * 1_000_000 / (u128::from(CLOCK_TICKS_PER_SEC) * millis);
+    Some(u32::try_from(tenths).unwrap_or(u32::MAX))
+}
+
+/// Fill in each pane's git `branch`, reading each distinct directory once
+pub fn fill_branches(sessions: &mut [TmuxSession]) {
+    let mut cache: HashMap<String, Option<String>> = HashMap::new();
+    for pane in sessions
+        .iter_mut()
+        .flat_map(|s| s.windows.iter_mut())
+        .flat_map(|w| w.panes.iter_mut())
+    {
+        pane.branch = cache
+            .entry(pane.current_path.clone())
+            .or_insert_with(|| git::branch(Path::new(&pane.current_path)))
+            .clone();
+    }
+}
+
+/// One row of `ps`: (pid, parent pid, full command line)
+type ProcessRow = (u32, u32, String);
+
+fn process_table() -> Option<Vec<ProcessRow>> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,args="])
+        .output()
+        .ok()?;
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(eyre!("Failed to create session: {}", stderr.trim()));
+        return None;
+    }
+    Some(parse_process_table(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn parse_process_table(output: &str) -> Vec<ProcessRow> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let args = fields.collect::<Vec<_>>().join(" ");
+            Some((pid, ppid, args))
+        })
+        .collect()
+}
+
+/// Follow the newest child from `pid` down to a leaf process
+fn foreground_process(table: &[ProcessRow], pid: u32) -> Option<&ProcessRow> {
+    let mut current = table.iter().find(|(p, _, _)| *p == pid)?;
+    while let Some(child) = table
+        .iter()
+        .filter(|(_, ppid, _)| *ppid == current.0)
+        .max_by_key(|(p, _, _)| *p)
+    {
+        current = child;
+    }
+    Some(current)
+}
+
+/// Create a detached session, starting in `start_dir` if given
+pub fn create_session(name: &str, start_dir: Option<&str>) -> Result<()> {
+    let mut args = vec!["new-session", "-d", "-s", name];
+    if let Some(dir) = start_dir {
+        args.extend(["-c", dir]);
+    }
+    run_mutation(&args, "create session")
+}
+
+/// Why tmux would refuse or mangle session name `name`, if it would.
+/// '.' and ':' separate window and pane in targets, so tmux rejects them.
+pub fn validate_session_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Name can't be empty".to_string());
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '.' | ':')) {
+        return Err(format!("Name can't contain '{c}'"));
     }
     Ok(())
 }
 
+/// Whether a session is named exactly `name` (no prefix matching)
+pub fn has_session(name: &str) -> bool {
+    output(tmux_command().args(["has-session", "-t", &format!("={name}")]))
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Create session `name` unless it already exists; true if it was created.
+/// Like `new-session -A`, minus the attach, which the caller does.
+pub fn ensure_session(name: &str, start_dir: Option<&str>) -> Result<bool> {
+    if has_session(name) {
+        return Ok(false);
+    }
+    create_session(name, start_dir)?;
+    Ok(true)
+}
+
 pub fn kill_session(name: &str) -> Result<()> {
-    let output = Command::new("tmux")
-        .args(["kill-session", "-t", name])
-        .output()
-        .wrap_err("Failed to kill tmux session")?;
+    run_mutation(&["kill-session", "-t", name], "kill session")
+}
+
+/// Detach every client attached to session `name`
+pub fn detach_clients(name: &str) -> Result<()> {
+    run_mutation(&["detach-client", "-s", name], "detach clients")
+}
+
+/// Kill the whole tmux server and every session on it
+pub fn kill_server() -> Result<()> {
+    run_mutation(&["kill-server"], "kill server")
+}
+
+pub fn rename_session(old_name: &str, new_name: &str) -> Result<()> {
+    run_mutation(
+        &["rename-session", "-t", old_name, new_name],
+        "rename session",
+    )
+}
+
+/// Move window `src` ("session:index") to the end of session `dst`
+pub fn move_window(src: &str, dst: &str) -> Result<()> {
+    run_mutation(
+        &["move-window", "-s", src, "-t", &format!("{dst}:")],
+        "move window",
+    )
+}
+
+/// Swap windows `a` and `b` of `session` without changing its active window
+pub fn swap_window(session: &str, a: u32, b: u32) -> Result<()> {
+    run_mutation(
+        &[
+            "swap-window",
+            "-d",
+            "-s",
+            &format!("{session}:{a}"),
+            "-t",
+            &format!("{session}:{b}"),
+        ],
+        "swap windows",
+    )
+}
+
+/// Close the gaps in `session`'s window indices, numbering from `base-index`
+pub fn renumber_windows(session: &str) -> Result<()> {
+    run_mutation(&["move-window", "-r", "-t", session], "renumber windows")
+}
+
+/// Window indices of `session`, in order
+pub fn window_indices(session: &str) -> Result<Vec<u32>> {
+    let output =
+        output(tmux_command().args(["list-windows", "-t", session, "-F", "#{window_index}"]))
+            .wrap_err("Failed to run tmux list-windows")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(eyre!("Failed to kill session: {}", stderr.trim()));
+        return Err(eyre!("Failed to list windows: {}", stderr.trim()));
     }
-    Ok(())
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect())
 }
 
-pub fn rename_session(old_name: &str, new_name: &str) -> Result<()> {
-    let output = Command::new("tmux")
-        .args(["rename-session", "-t", old_name, new_name])
-        .output()
-        .wrap_err("Failed to rename tmux session")?;
+/// Link window `src` ("session:index") into session `dst` as well, at its next free index
+pub fn link_window(src: &str, dst: &str) -> Result<()> {
+    run_mutation(
+        &["link-window", "-s", src, "-t", &format!("{dst}:")],
+        "link window",
+    )
+}
+
+/// Add a window to `session` and make it current, named `name` if given
+pub fn new_window(session: &str, name: Option<&str>, start_dir: Option<&str>) -> Result<()> {
+    let target = format!("{session}:");
+    let mut args = vec!["new-window", "-t", &target];
+    if let Some(name) = name {
+        args.extend(["-n", name]);
+    }
+    if let Some(dir) = start_dir {
+        args.extend(["-c", dir]);
+    }
+    run_mutation(&args, "create window")
+}
+
+/// Split the active pane of `session`'s current window; the new pane becomes active
+pub fn split_window(session: &str, start_dir: Option<&str>) -> Result<()> {
+    let target = format!("{session}:");
+    let mut args = vec!["split-window", "-t", &target];
+    if let Some(dir) = start_dir {
+        args.extend(["-c", dir]);
+    }
+    run_mutation(&args, "split window")
+}
+
+/// Arrange the panes of `session`'s current window, e.g. "main-vertical"
+pub fn select_layout(session: &str, layout: &str) -> Result<()> {
+    run_mutation(
+        &["select-layout", "-t", &format!("{session}:"), layout],
+        "select layout",
+    )
+}
+
+pub fn rename_window(target: &str, name: &str) -> Result<()> {
+    run_mutation(&["rename-window", "-t", target, name], "rename window")
+}
+
+/// Make window `target` ("session:index") the current window of its session
+pub fn select_window(target: &str) -> Result<()> {
+    run_mutation(&["select-window", "-t", target], "select window")
+}
+
+/// Make pane `target` ("session:window.pane") the active pane of its window
+pub fn select_pane(target: &str) -> Result<()> {
+    run_mutation(&["select-pane", "-t", target], "select pane")
+}
+
+/// Break pane `target` ("session:window.pane") out into its own window
+pub fn break_pane(target: &str) -> Result<()> {
+    run_mutation(&["break-pane", "-d", "-s", target], "break pane")
+}
+
+/// Restart the command in pane `target`, killing it first if it still runs
+pub fn respawn_pane(target: &str) -> Result<()> {
+    run_mutation(&["respawn-pane", "-k", "-t", target], "respawn pane")
+}
+
+/// Restart window `target` ("session:index") as a single pane running its
+/// original command, killing anything still running in it
+pub fn respawn_window(target: &str) -> Result<()> {
+    run_mutation(&["respawn-window", "-k", "-t", target], "respawn window")
+}
+
+/// Turn `synchronize-panes` on or off for window `target` ("session:index")
+pub fn set_synchronize_panes(target: &str, on: bool) -> Result<()> {
+    run_mutation(
+        &[
+            "set-window-option",
+            "-t",
+            target,
+            "synchronize-panes",
+            if on { "on" } else { "off" },
+        ],
+        "set synchronize-panes",
+    )
+}
+
+/// The last `lines` lines of pane `target`, reaching into its history when
+/// the visible screen is shorter. Colors are kept as escape sequences (`-e`).
+pub fn capture_pane(target: &str, lines: u16) -> Result<String> {
+    let start = format!("-{lines}");
+    let output =
+        output(tmux_command().args(["capture-pane", "-e", "-p", "-S", &start, "-t", target]))
+            .wrap_err("Failed to capture tmux pane")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(eyre!("Failed to rename session: {}", stderr.trim()));
+        return Err(eyre!("Failed to capture pane: {}", stderr.trim()));
     }
-    Ok(())
+    let text = String::from_utf8_lossy(&output.stdout);
+    // The screen below the last output is blank lines
+    let all: Vec<&str> = text.trim_end().lines().collect();
+    let skip = all.len().saturating_sub(usize::from(lines));
+    Ok(all[skip..].join("\n"))
+}
+
+/// Full contents of pane `target`, from the start of its history
+pub fn capture_pane_full(target: &str) -> Result<String> {
+    let output = output(tmux_command().args(["capture-pane", "-p", "-S", "-", "-t", target]))
+        .wrap_err("Failed to capture tmux pane")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("Failed to capture pane: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// What the running server reports about itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub pid: u32,
+    /// When the server started, in Unix seconds
+    pub start_time: u64,
+    /// Config files the server loaded, in load order; empty when none were
+    pub config_files: Vec<String>,
+}
+
+pub fn server_info() -> Result<ServerInfo> {
+    let output = output(tmux_command().args([
+        "display-message",
+        "-p",
+        "#{pid}|#{start_time}|#{config_files}",
+    ]))
+    .wrap_err("Failed to run tmux display-message")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("Failed to get server info: {}", stderr.trim()));
+    }
+    parse_server_info(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_server_info(output: &str) -> Result<ServerInfo> {
+    let mut fields = output.trim().splitn(3, '|');
+    let (Some(pid), Some(start_time), Some(files)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(eyre!("Unexpected server info: {output:?}"));
+    };
+    Ok(ServerInfo {
+        pid: pid.parse().wrap_err("Failed to parse server pid")?,
+        start_time: start_time
+            .parse()
+            .wrap_err("Failed to parse server start time")?,
+        config_files: files
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+/// Session `name`'s environment from `show-environment`. Variables the
+/// session removes from the global environment keep tmux's leading '-'
+/// and have an empty value.
+pub fn show_environment(name: &str) -> Result<Vec<(String, String)>> {
+    let output = output(tmux_command().args(["show-environment", "-t", name]))
+        .wrap_err("Failed to run tmux show-environment")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("Failed to show environment: {}", stderr.trim()));
+    }
+    Ok(parse_environment(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_environment(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (line.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Type `keys` literally into pane `target`, then press Enter.
+/// The text goes in as one argument with `-l`, so nothing is split or
+/// interpreted as a key name. Both go in one invocation, so `--dry-run`
+/// records the Enter too.
+pub fn send_keys(target: &str, keys: &str) -> Result<()> {
+    run_mutation(
+        &[
+            "send-keys",
+            "-t",
+            target,
+            "-l",
+            "--",
+            keys,
+            ";",
+            "send-keys",
+            "-t",
+            target,
+            "Enter",
+        ],
+        "send keys",
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_send_keys_dry_run() {
+        set_dry_run(true);
+        let result = send_keys("dev:1.0", "echo hi");
+        set_dry_run(false);
+        assert!(result.is_err_and(|e| e.downcast_ref::<DryRun>().is_some()));
+
+        let log = take_dry_run_log();
+        assert_eq!(log.len(), 1);
+        assert!(
+            log[0].ends_with("send-keys -t dev:1.0 -l -- 'echo hi' ';' send-keys -t dev:1.0 Enter")
+        );
+    }
+
     #[test]
     fn test_parse_sessions() {
         let output = "dev|$0|1|2|1700000000|0|zsh|1|0|zsh|/home/user|1\n\
@@ -224,16 +1263,282 @@ mod tests {
         assert_eq!(sessions[1].windows[0].panes.len(), 2);
     }
 
+    #[test]
+    fn test_parse_pane_extras() {
+        let output =
+            "dev|$0|1|1|1700000000|0|zsh|1|0|zsh|/home/user|1|4242|box|1700000500|1|0|1700000400|1|build | watch\n\
+                       dev|$0|1|1|1700000000|0|zsh|1|1|zsh|/home/user|0|4243|box|1700000500|1|1|1700000400|1|box\n";
+
+        let sessions = parse_sessions(output).unwrap();
+        assert_eq!(sessions[0].idle_secs(1700003500), 3000);
+        assert!(sessions[0].windows[0].synchronized);
+        assert_eq!(sessions[0].windows[0].activity, 1700000400);
+        assert!(sessions[0].windows[0].linked);
+        let panes = &sessions[0].windows[0].panes;
+        assert_eq!(panes[0].pid, 4242);
+        assert_eq!(panes[0].display_title(), Some("build | watch"));
+        assert_eq!(panes[1].display_title(), None);
+        assert!(!panes[0].dead);
+        assert!(panes[1].dead);
+    }
+
+    #[test]
+    fn test_parse_environment() {
+        let env = parse_environment("DISPLAY=:0\n-SSH_AGENT_PID\nOPTS=a=b\n");
+        assert_eq!(
+            env,
+            vec![
+                ("DISPLAY".to_string(), ":0".to_string()),
+                ("-SSH_AGENT_PID".to_string(), String::new()),
+                ("OPTS".to_string(), "a=b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_server_info() {
+        let info =
+            parse_server_info("4242|1760000000|/home/user/.tmux.conf,/home/user/.tmux.local\n")
+                .unwrap();
+        assert_eq!(info.pid, 4242);
+        assert_eq!(info.start_time, 1_760_000_000);
+        assert_eq!(
+            info.config_files,
+            vec!["/home/user/.tmux.conf", "/home/user/.tmux.local"]
+        );
+        assert!(parse_server_info("4242|1760000000|\n")
+            .unwrap()
+            .config_files
+            .is_empty());
+        assert!(parse_server_info("4242|\n").is_err());
+    }
+
+    #[test]
+    fn test_foreground_process() {
+        let table = parse_process_table(
+            "  100     1 -zsh\n  200   100 cargo watch -x test\n  300   200 rustc --edition 2021\n",
+        );
+        assert_eq!(
+            foreground_process(&table, 100),
+            Some(&(300, 200, "rustc --edition 2021".to_string()))
+        );
+        assert_eq!(foreground_process(&table, 999), None);
+    }
+
+    #[test]
+    fn test_parse_proc_usage() {
+        let stat = "4242 (tmux: server) S 1 4242 4242 0 -1 4194560 1021 0 0 0 \
+                    250 75 0 0 20 0 1 0 123456 10485760 2048 18446744073709551615";
+        assert_eq!(parse_stat_ticks(stat), Some(325));
+        assert_eq!(parse_stat_ticks("garbage"), None);
+
+        let status = "Name:\tcargo\nVmPeak:\t  20000 kB\nVmRSS:\t   12345 kB\n";
+        assert_eq!(parse_status_rss(status), Some(12345));
+        assert_eq!(parse_status_rss("Name:\tkthreadd\n"), None);
+    }
+
+    #[test]
+    fn test_cpu_tenths() {
+        // 200 ticks = 2s of CPU over 2s of wall time: one full core
+        assert_eq!(cpu_tenths(200, Duration::from_secs(2)), Some(1000));
+        assert_eq!(cpu_tenths(5, Duration::from_secs(2)), Some(25));
+        assert_eq!(cpu_tenths(0, Duration::from_secs(2)), Some(0));
+        assert_eq!(cpu_tenths(10, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("tmux 3.3a"), Some((3, 3)));
+        assert_eq!(parse_version("tmux 3.4\n"), Some((3, 4)));
+        assert_eq!(parse_version("tmux next-3.5"), Some((3, 5)));
+        assert_eq!(parse_version("tmux 1.8"), Some((1, 8)));
+        assert_eq!(parse_version("tmux master"), None);
+        assert!(parse_version("tmux 1.8").unwrap() < MIN_VERSION);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("kill-session"), "kill-session");
+        assert_eq!(shell_quote("dev:1.0"), "dev:1.0");
+        assert_eq!(shell_quote("make test"), "'make test'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
     #[test]
     fn test_parse_empty() {
         let sessions = parse_sessions("").unwrap();
         assert!(sessions.is_empty());
     }
 
+    #[test]
+    fn test_search_panes() {
+        let output = "dev|$0|1|2|1700000000|0|zsh|1|0|zsh|/home/user|1\n\
+                       dev|$0|1|2|1700000000|1|cargo|0|0|cargo|/home/user/project|1\n\
+                       scratch|$1|0|1|1700000001|0|vim|1|1|bash|/tmp/Project|0\n";
+        let sessions = parse_sessions(output).unwrap();
+
+        let found = search_panes(&sessions, "PROJECT");
+        let targets: Vec<_> = found
+            .iter()
+            .map(|m| (m.session.as_str(), m.window, m.pane))
+            .collect();
+        assert_eq!(targets, vec![("dev", 1, 0), ("scratch", 0, 1)]);
+        assert_eq!(search_panes(&sessions, "cargo")[0].command, "cargo");
+        assert_eq!(search_panes(&sessions, "").len(), 3);
+        assert!(search_panes(&sessions, "htop").is_empty());
+
+        // Scattered letters still match, the closest fit first
+        let found = search_panes(&sessions, "tmpj");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].field, Some(MatchField::Path));
+        let found = search_panes(&sessions, "zh");
+        assert_eq!((found[0].session.as_str(), found[0].window), ("dev", 0));
+        assert_eq!(found[0].field, Some(MatchField::Command));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("anything", ""), Some((0, vec![])));
+        assert_eq!(fuzzy_match("cargo", "xyz"), None);
+        assert_eq!(fuzzy_match("cargo", "CGO").unwrap().1, vec![0, 3, 4]);
+        // A later run of consecutive letters beats the first scattered one
+        assert_eq!(fuzzy_match("a-b-abc", "abc").unwrap().1, vec![4, 5, 6]);
+        // Word starts beat letters mid-word
+        let (start, _) = fuzzy_match("my-project", "p").unwrap();
+        let (middle, _) = fuzzy_match("sleep", "p").unwrap();
+        assert!(start > middle);
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let output = "dev|$0|1|2|1700000000|0|zsh|0|0|zsh|/home/user|1\n\
+                       dev|$0|1|2|1700000000|3|cargo|1|0|zsh|/home/user/project|0\n\
+                       dev|$0|1|2|1700000000|3|cargo|1|1|cargo|/home/user/project|1\n";
+        let sessions = parse_sessions(output).unwrap();
+        let sessions: Vec<&TmuxSession> = sessions.iter().collect();
+        let path = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let resolve = |ids: &[&str]| resolve_path(&sessions, &path(ids), false);
+
+        // Paths that still exist are kept as they are
+        assert_eq!(resolve(&["dev"]), Some(path(&["dev"])));
+        assert_eq!(resolve(&["dev", "0"]), Some(path(&["dev", "0"])));
+        assert_eq!(resolve(&["dev", "3", "0"]), Some(path(&["dev", "3", "0"])));
+        // A closed window falls back to the active one and its active pane
+        assert_eq!(resolve(&["dev", "7"]), Some(path(&["dev", "3"])));
+        assert_eq!(resolve(&["dev", "7", "0"]), Some(path(&["dev", "3", "1"])));
+        // A closed pane falls back to the window's active pane
+        assert_eq!(resolve(&["dev", "3", "5"]), Some(path(&["dev", "3", "1"])));
+        // A single-pane window has no pane rows unless all panes are listed
+        assert_eq!(resolve(&["dev", "0", "0"]), Some(path(&["dev", "0"])));
+        assert_eq!(
+            resolve_path(&sessions, &path(&["dev", "0", "4"]), true),
+            Some(path(&["dev", "0", "0"]))
+        );
+        assert_eq!(resolve(&["gone", "0"]), None);
+        assert_eq!(resolve(&[]), None);
+    }
+
+    #[test]
+    fn test_window_label() {
+        let output = "dev|$0|1|2|1700000000|0|zsh|1|0|vim|/home/user|1\n\
+                       dev|$0|1|2|1700000000|1|zsh|0|0|zsh|/home/user|1\n\
+                       dev|$0|1|2|1700000000|2|editor|0|0|vim|/home/user|1\n";
+        let sessions = parse_sessions(output).unwrap();
+        let windows = &sessions[0].windows;
+
+        assert_eq!(windows[0].label(Some("zsh")), "vim");
+        // Nothing running but the shell, or a name the user chose, stays
+        assert_eq!(windows[1].label(Some("zsh")), "zsh");
+        assert_eq!(windows[2].label(Some("zsh")), "editor");
+        assert_eq!(windows[0].label(Some("bash")), "zsh");
+        assert_eq!(windows[0].label(None), "zsh");
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("vim\tmain.rs   "), "vim main.rs");
+        assert_eq!(normalize_name("  build\x1b  log\n"), "build log");
+        assert_eq!(normalize_name("zsh"), "zsh");
+    }
+
+    #[test]
+    fn test_parse_duplicate_panes() {
+        // The same window linked into two sessions shows up under both,
+        // and a repeated pane line must not produce a second pane 0
+        let output = "dev|$0|1|1|1700000000|2|zsh|1|0|zsh|/home/user|1\n\
+                       dev|$0|1|1|1700000000|2|zsh|1|0|zsh|/home/user|1\n\
+                       ops|$1|0|1|1700000001|2|zsh|1|0|zsh|/home/user|1\n";
+
+        let sessions = parse_sessions(output).unwrap();
+        assert_eq!(sessions.len(), 2);
+        for session in &sessions {
+            assert_eq!(session.windows.len(), 1);
+            assert_eq!(session.windows[0].panes.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_validate_session_name() {
+        assert!(validate_session_name("dev").is_ok());
+        assert!(validate_session_name("proj/api").is_ok());
+        assert!(validate_session_name("my session").is_ok());
+        assert_eq!(
+            validate_session_name("v1.2"),
+            Err("Name can't contain '.'".to_string())
+        );
+        assert_eq!(
+            validate_session_name("proj:api"),
+            Err("Name can't contain ':'".to_string())
+        );
+        assert!(validate_session_name("  ").is_err());
+    }
+
+    #[test]
+    fn test_session_group() {
+        assert_eq!(session_group("proj/api"), Some("proj"));
+        assert_eq!(session_group("proj:web"), Some("proj"));
+        assert_eq!(session_group("scratch"), None);
+        assert_eq!(session_group("/odd"), None);
+    }
+
+    #[test]
+    fn test_fill_template() {
+        let fields = [("command", "vim"), ("path", "~/code"), ("panes", "2")];
+        assert_eq!(fill_template("{command}  {path}", &fields), "vim  ~/code");
+        assert_eq!(fill_template("{panes}p {command}", &fields), "2p vim");
+        assert_eq!(fill_template("{nope} {path}", &fields), "{nope} ~/code");
+        assert_eq!(fill_template("{path} {", &fields), "~/code {");
+    }
+
     #[test]
     fn test_shorten_path() {
         std::env::set_var("HOME", "/home/user");
         assert_eq!(shorten_path("/home/user/code"), "~/code");
         assert_eq!(shorten_path("/tmp/foo"), "/tmp/foo");
     }
+
+    #[test]
+    fn test_shorten_path_home_boundary() {
+        assert_eq!(
+            shorten_path_with_home("/home/user/x", Some("/home/u")),
+            "/home/user/x"
+        );
+        assert_eq!(shorten_path_with_home("/home/u", Some("/home/u")), "~");
+        assert_eq!(shorten_path_with_home("/home/u/x", Some("/home/u/")), "~/x");
+        assert_eq!(shorten_path_with_home("/home/u/x", None), "/home/u/x");
+        assert_eq!(shorten_path_with_home("/etc", Some("/")), "/etc");
+    }
+
+    #[test]
+    fn test_shorten_path_fish() {
+        std::env::set_var("HOME", "/home/user");
+        assert_eq!(
+            shorten_path_fish("/home/user/code/projects/tmxu"),
+            "~/c/p/tmxu"
+        );
+        assert_eq!(shorten_path_fish("/home/user/.config/fish"), "~/.c/fish");
+        assert_eq!(shorten_path_fish("/usr/local/bin"), "/u/l/bin");
+        assert_eq!(shorten_path_fish("/home/user"), "~");
+    }
 }
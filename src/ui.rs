@@ -1,4 +1,5 @@
 use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use ratatui::layout::{Alignment, Constraint, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
@@ -6,9 +7,11 @@ use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
 use ratatui::Frame;
 use tui_tree_widget::{Tree, TreeItem};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::app::{App, Mode};
-use crate::tmux::{self, TmuxSession};
+use crate::app::{App, FlashMessage, Level, MenuItem, Mode};
+use crate::cli::StatusPosition;
+use crate::tmux::{self, MatchField, TmuxSession, TmuxWindow};
 
 // BBS/warez color palette
 const CYAN: Color = Color::Cyan;
@@ -17,6 +20,36 @@ const GREEN: Color = Color::Green;
 const YELLOW: Color = Color::Yellow;
 const DIM: Color = Color::DarkGray;
 const WHITE: Color = Color::White;
+const RED: Color = Color::Red;
+
+/// Palette for `--hash-colors` session names
+const NAME_PALETTE: [Color; 8] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::LightRed,
+    Color::LightCyan,
+    Color::LightMagenta,
+];
+
+/// A session's windows in display order with the number each is labeled
+/// `[n]` with: tmux's window index, or its 1-based position. The number keys
+/// go through this too, so a label always selects what it's shown on.
+pub fn labeled_windows(
+    windows: &[TmuxWindow],
+    tmux_indices: bool,
+) -> impl Iterator<Item = (usize, &TmuxWindow)> {
+    windows.iter().enumerate().map(move |(pos, window)| {
+        let number = if tmux_indices {
+            window.index as usize
+        } else {
+            pos + 1
+        };
+        (number, window)
+    })
+}
 
 /// Render the hostname banner once using tui-banner with Royal Purple style.
 /// Returns ratatui Text for embedding in the header widget.
@@ -34,6 +67,13 @@ pub fn render_banner() -> Text<'static> {
     };
 
     let mut text = ansi.into_text().unwrap_or_else(|_| Text::raw(hostname));
+    // The gradient is 24-bit; elsewhere it turns into muddy approximations
+    if !supports_truecolor() {
+        for span in text.lines.iter_mut().flat_map(|l| l.spans.iter_mut()) {
+            span.style.fg = span.style.fg.map(to_ansi);
+            span.style.bg = span.style.bg.map(to_ansi);
+        }
+    }
     // Trim trailing blank lines from tui-banner output
     while text
         .lines
@@ -45,6 +85,46 @@ pub fn render_banner() -> Text<'static> {
     text
 }
 
+/// Whether the terminal advertises 24-bit color (`COLORTERM=truecolor` or `24bit`)
+pub fn supports_truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit")
+}
+
+/// The 16 ANSI colors with their usual xterm RGB values
+const ANSI_RGB: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Nearest ANSI color to a 24-bit one; other colors pass through
+pub fn to_ansi(color: Color) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    let distance = |(ar, ag, ab): (u8, u8, u8)| {
+        let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2);
+        d(r, ar) + d(g, ag) + d(b, ab)
+    };
+    ANSI_RGB
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map_or(color, |(ansi, _)| *ansi)
+}
+
 fn get_hostname() -> String {
     Command::new("hostname")
         .arg("-s")
@@ -55,47 +135,189 @@ fn get_hostname() -> String {
         .unwrap_or_else(|| "tmu".to_string())
 }
 
+/// Height of the status bar (flash line + keybinds + top border)
+const STATUS_HEIGHT: u16 = 3;
+/// Rows the terminal must have beyond the banner for the banner to be shown
+const BANNER_MIN_SPARE_ROWS: u16 = 8;
+
+/// Smallest terminal the normal layout is drawn in
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 6;
+
 /// Main draw function — renders entire UI from app state
 pub fn draw(frame: &mut Frame, app: &mut App) {
-    let (tree_area, status_area) = if let Some(ref banner) = app.banner {
-        let header_height = banner.height() as u16 + 1; // +1 for bottom border
-        let chunks = Layout::vertical([
-            Constraint::Length(header_height),
-            Constraint::Min(3),
-            Constraint::Length(3),
+    let area = frame.area();
+    app.screen_width = area.width;
+    app.screen_height = area.height;
+
+    // Below this the layout squeezes the tree to nothing
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        let message = Paragraph::new(vec![
+            Line::from(Span::styled(
+                "Terminal too small",
+                Style::default().fg(YELLOW),
+            )),
+            Line::from(Span::styled(
+                format!("need {MIN_WIDTH}×{MIN_HEIGHT}"),
+                Style::default().fg(DIM),
+            )),
         ])
-        .split(frame.area());
-        draw_header(frame, banner, chunks[0]);
-        (chunks[1], chunks[2])
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+        let top = area.height.saturating_sub(2) / 2;
+        frame.render_widget(
+            message,
+            Rect {
+                y: area.y + top,
+                height: area.height - top,
+                ..area
+            },
+        );
+        return;
+    }
+
+    // Drop the banner on short terminals so the tree keeps the space
+    let header_height = match app.banner {
+        Some(ref banner) => {
+            let height = banner.height() as u16 + 1; // +1 for bottom border
+            if area.height >= height + BANNER_MIN_SPARE_ROWS {
+                height
+            } else {
+                0
+            }
+        }
+        None => 0,
+    };
+
+    let status_top = app.layout == StatusPosition::Top;
+    let header = Constraint::Length(header_height);
+    let tree = Constraint::Min(0);
+    let status = Constraint::Length(STATUS_HEIGHT + u16::from(app.inside_tmux));
+    let (header_area, tree_area, status_area) = if status_top {
+        let [status_area, tree_area, header_area] =
+            Layout::vertical([status, tree, header]).areas(area);
+        (header_area, tree_area, status_area)
     } else {
-        let chunks =
-            Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).split(frame.area());
-        (chunks[0], chunks[1])
+        let [header_area, tree_area, status_area] =
+            Layout::vertical([header, tree, status]).areas(area);
+        (header_area, tree_area, status_area)
     };
+    if let Some(ref banner) = app.banner {
+        if header_height > 0 {
+            // Below the tree, the tree's own bottom border separates them
+            let borders = if status_top {
+                Borders::NONE
+            } else {
+                Borders::BOTTOM
+            };
+            draw_header(frame, banner, header_area, borders);
+        }
+    }
 
     draw_tree(frame, app, tree_area);
     draw_status_bar(frame, app, status_area);
 
     // Draw popups on top
     match &app.mode {
-        Mode::CreateSession { input } => {
-            draw_input_popup(frame, "New Session", input);
+        Mode::CreateSession {
+            input,
+            start_dir,
+            error,
+        } => {
+            let title = match start_dir {
+                Some(dir) => format!("New Session in {}", tmux::shorten_path(dir)),
+                None if app.server_running => "New Session".to_string(),
+                None => "Start tmux: First Session".to_string(),
+            };
+            draw_input_popup(
+                frame,
+                &title,
+                input,
+                Some("Enter: create · Alt+Enter: attach, creating if needed"),
+                error.as_deref(),
+            );
         }
-        Mode::RenameSession { target, input } => {
+        Mode::RenameSession {
+            target,
+            input,
+            error,
+            inline: false,
+        } => {
             let title = format!("Rename '{target}'");
-            draw_input_popup(frame, &title, input);
+            let hint = tmux::session_group(target).map(|_| "Tab: keep prefix, retype the rest");
+            draw_input_popup(frame, &title, input, hint, error.as_deref());
+        }
+        Mode::ConfirmKill {
+            target,
+            windows,
+            panes,
+        } => {
+            draw_confirm_popup(
+                frame,
+                "Confirm Kill",
+                "Kill session",
+                Some(target.as_str()),
+                Some(&blast_radius(*windows, *panes)),
+            );
         }
-        Mode::ConfirmKill { target } => {
-            draw_confirm_popup(frame, target);
+        Mode::ConfirmKillServer { input } => {
+            draw_input_popup(
+                frame,
+                "Type 'yes' to kill the tmux server",
+                input,
+                None,
+                None,
+            );
+        }
+        Mode::ConfirmQuit => {
+            draw_confirm_popup(frame, "Confirm Quit", "Quit tmxu", None, None);
+        }
+        Mode::EditNote { target, input } => {
+            let title = format!("Note for '{target}'");
+            draw_input_popup(frame, &title, input, None, None);
+        }
+        Mode::SendKeys { target, input } => {
+            let title = format!("Send to '{target}'");
+            draw_input_popup(frame, &title, input, None, None);
+        }
+        Mode::Error { message, scroll } => {
+            draw_error_popup(frame, message, *scroll);
+        }
+        Mode::Environment {
+            session,
+            vars,
+            scroll,
+        } => {
+            draw_environment_popup(frame, session, vars, *scroll);
+        }
+        Mode::Preview { target, text } => {
+            draw_preview_popup(frame, target, text, app.preview_lines);
+        }
+        Mode::Search { query, selected } => {
+            draw_search_popup(frame, &app.sessions, query, *selected);
+        }
+        Mode::MoveWindow { src, link } => {
+            draw_move_popup(
+                frame,
+                &app.visible_sessions(),
+                src,
+                *link,
+                app.pending_label(),
+            );
         }
+        Mode::Menu { selected } => {
+            draw_menu_popup(frame, *selected);
+        }
+        // Drawn by `build_tree_items` on the session's row
+        Mode::RenameSession { inline: true, .. } => {}
         Mode::Normal => {}
     }
 }
 
-fn draw_header(frame: &mut Frame, banner: &Text, area: Rect) {
+fn draw_header(frame: &mut Frame, banner: &Text, area: Rect, borders: Borders) {
     let header = Paragraph::new(banner.clone()).block(
         Block::default()
-            .borders(Borders::BOTTOM)
+            .borders(borders)
             .border_style(Style::default().fg(DIM)),
     );
     frame.render_widget(header, area);
@@ -103,17 +325,22 @@ fn draw_header(frame: &mut Frame, banner: &Text, area: Rect) {
 
 fn draw_tree(frame: &mut Frame, app: &mut App, area: Rect) {
     if app.sessions.is_empty() {
+        let (message, hint) = if app.server_running {
+            (
+                "  No tmux sessions found.",
+                "  Press n to create a new session.",
+            )
+        } else {
+            (
+                "  The tmux server is not running.",
+                "  Press n to start it with a first session.",
+            )
+        };
         let empty = Paragraph::new(vec![
             Line::from(""),
-            Line::from(Span::styled(
-                "  No tmux sessions found.",
-                Style::default().fg(DIM),
-            )),
+            Line::from(Span::styled(message, Style::default().fg(DIM))),
             Line::from(""),
-            Line::from(Span::styled(
-                "  Press n to create a new session.",
-                Style::default().fg(YELLOW),
-            )),
+            Line::from(Span::styled(hint, Style::default().fg(YELLOW))),
         ])
         .block(
             Block::default()
@@ -124,9 +351,20 @@ fn draw_tree(frame: &mut Frame, app: &mut App, area: Rect) {
         return;
     }
 
-    let items = build_tree_items(&app.sessions);
-    let tree = Tree::new(&items)
-        .expect("unique identifiers")
+    // Identifier clashes (odd server state) degrade to a message, never a panic
+    let items = match build_tree_items(app, area.width) {
+        Ok(items) => items,
+        Err(e) => return draw_tree_error(frame, app, area, &e),
+    };
+    let tree = match Tree::new(&items) {
+        Ok(tree) => tree,
+        Err(e) => return draw_tree_error(frame, app, area, &e),
+    };
+    app.render_error = None;
+    // Rows of tree under the bottom border, which PageUp/PageDown move by
+    app.tree_height = area.height.saturating_sub(1);
+
+    let tree = tree
         .block(
             Block::default()
                 .borders(Borders::BOTTOM)
@@ -138,30 +376,219 @@ fn draw_tree(frame: &mut Frame, app: &mut App, area: Rect) {
                 .bg(Color::DarkGray)
                 .add_modifier(Modifier::BOLD),
         )
-        .highlight_symbol(">> ")
-        .node_closed_symbol("▸ ")
-        .node_open_symbol("▾ ")
-        .node_no_children_symbol("  ");
+        .highlight_symbol(&app.symbols.highlight)
+        .node_closed_symbol(&app.symbols.closed)
+        .node_open_symbol(&app.symbols.open)
+        .node_no_children_symbol(&app.symbols.leaf);
 
     frame.render_stateful_widget(tree, area, &mut app.tree_state);
 }
 
-/// Map session index (0-based) to a label letter A-Z
-fn session_label(idx: usize) -> char {
+fn draw_tree_error(frame: &mut Frame, app: &mut App, area: Rect, err: &std::io::Error) {
+    let message = format!("Cannot display sessions: {err}");
+    // Flash once per distinct error rather than on every redraw
+    if app.render_error.as_deref() != Some(message.as_str()) {
+        app.push_flash(FlashMessage::error(message.clone()));
+        app.render_error = Some(message.clone());
+    }
+
+    let text = Paragraph::new(vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            format!("  {message}"),
+            Style::default().fg(RED),
+        )),
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::BOTTOM)
+            .border_style(Style::default().fg(DIM)),
+    );
+    frame.render_widget(text, area);
+}
+
+/// Sessions idle for less than this (seconds) don't show an idle time
+const IDLE_DISPLAY_THRESHOLD: u64 = 60;
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Compact age in the largest whole unit: "45s", "12m", "3h", "2d"
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{s}s"),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 60 * 60 * 24 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (60 * 60 * 24)),
+    }
+}
+
+/// Columns of indentation the tree widget adds per nesting level
+const TREE_INDENT: usize = 2;
+
+/// Fit a row into `width` columns. Other rows get a middle ellipsis in their
+/// `flex` span; the selected row keeps its full text, scrolled by `scroll`.
+/// Lengths are display widths, so wide (CJK, emoji) characters count double.
+fn fit_row(
+    mut spans: Vec<Span<'static>>,
+    flex: usize,
+    width: usize,
+    scroll: Option<usize>,
+) -> Line<'static> {
+    let total: usize = spans.iter().map(Span::width).sum();
+    match scroll {
+        Some(offset) => {
+            let mut skip = offset.min(total.saturating_sub(width));
+            let spans = spans
+                .into_iter()
+                .filter_map(|span| {
+                    let len = span.width();
+                    if skip >= len {
+                        skip -= len;
+                        return None;
+                    }
+                    // A wide char straddling the offset is dropped whole
+                    let mut skipped = 0;
+                    let content: String = span
+                        .content
+                        .chars()
+                        .skip_while(|c| {
+                            let skipping = skipped < skip;
+                            skipped += c.width().unwrap_or(0);
+                            skipping
+                        })
+                        .collect();
+                    skip = 0;
+                    Some(Span::styled(content, span.style))
+                })
+                .collect::<Vec<_>>();
+            Line::from(spans)
+        }
+        None if total > width => {
+            let span = &mut spans[flex];
+            let len = span.width();
+            span.content = truncate_middle(&span.content, len.saturating_sub(total - width)).into();
+            Line::from(spans)
+        }
+        None => Line::from(spans),
+    }
+}
+
+/// Shorten `s` to at most `max` columns by replacing its middle with '…'
+fn truncate_middle(s: &str, max: usize) -> String {
+    if s.width() <= max {
+        return s.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let keep = max - 1; // room for the ellipsis
+    let head = keep.div_ceil(2);
+    // Take whole chars from each end while they fit their half
+    let take = |chars: &mut dyn Iterator<Item = char>, budget: usize| {
+        let mut used = 0;
+        chars
+            .take_while(|c| {
+                used += c.width().unwrap_or(0);
+                used <= budget
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut out: String = take(&mut s.chars(), head).into_iter().collect();
+    out.push('…');
+    out.extend(take(&mut s.chars().rev(), keep - head).into_iter().rev());
+    out
+}
+
+/// First letters of two-letter labels: A-Z minus the letters normal mode
+/// binds (d, g, h, j, k, l, n, q, r), so every label can be typed there
+pub const LABEL_PREFIXES: &str = "ABCEFIMOPSTUVWXYZ";
+
+/// Map session index (0-based) to its label: A-Z, then AA, AB, … ZZ
+/// (first letter from `LABEL_PREFIXES`)
+fn session_label(idx: usize) -> String {
+    let letter = |i: usize| (b'A' + i as u8) as char;
     if idx < 26 {
-        (b'A' + idx as u8) as char
-    } else {
-        '?'
+        return letter(idx).to_string();
+    }
+    let i = idx - 26;
+    match LABEL_PREFIXES.as_bytes().get(i / 26) {
+        Some(&first) => format!("{}{}", first as char, letter(i % 26)),
+        None => "?".to_string(),
+    }
+}
+
+/// Session index for a label from `session_label`, in either case
+pub fn session_index(label: &str) -> Option<usize> {
+    let upper: Vec<char> = label.chars().map(|c| c.to_ascii_uppercase()).collect();
+    if !upper.iter().all(char::is_ascii_uppercase) {
+        return None;
+    }
+    let digit = |c: char| (c as u8 - b'A') as usize;
+    match upper.as_slice() {
+        [i] => Some(digit(*i)),
+        [i, j] => LABEL_PREFIXES.find(*i).map(|p| 26 + p * 26 + digit(*j)),
+        _ => None,
     }
 }
 
-/// Build tree items from session data for the tree widget
-fn build_tree_items(sessions: &[TmuxSession]) -> Vec<TreeItem<'static, String>> {
-    sessions
+/// Stable color for a session name. FNV-1a rather than `DefaultHasher`,
+/// whose output isn't guaranteed across Rust releases.
+fn name_color(name: &str) -> Color {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    NAME_PALETTE[(hash % NAME_PALETTE.len() as u64) as usize]
+}
+
+/// Tree identifier for a synthetic group node. The NUL prefix can't appear
+/// in a tmux session name, so it never collides with a session identifier.
+pub fn group_id(prefix: &str) -> String {
+    format!("\0{prefix}")
+}
+
+pub fn is_group_id(id: &str) -> bool {
+    id.starts_with('\0')
+}
+
+/// "12.5% 340M": CPU since the last refresh (blank until there is one) and RSS
+fn format_usage(usage: &tmux::ProcessUsage) -> String {
+    let rss = match usage.rss_kb {
+        kb if kb < 1024 => format!("{kb}K"),
+        kb if kb < 1024 * 1024 => format!("{}M", kb / 1024),
+        kb => format!("{:.1}G", kb as f64 / (1024.0 * 1024.0)),
+    };
+    match usage.cpu_tenths {
+        Some(tenths) => format!("{}.{}% {rss}", tenths / 10, tenths % 10),
+        None => rss,
+    }
+}
+
+/// Build tree items from session data for the tree widget.
+/// Fails if tmux reports duplicate identifiers at any level.
+fn build_tree_items(app: &App, width: u16) -> std::io::Result<Vec<TreeItem<'static, String>>> {
+    let sessions = app.visible_sessions();
+    let show_processes = app.show_processes;
+
+    let now = unix_now();
+    let selection = app.selection();
+    // Fit a row at tree `depth` whose identifier path (sans group) is `path`
+    let fit = |spans: Vec<Span<'static>>, flex: usize, depth: usize, path: &[&str]| {
+        let available = (width as usize).saturating_sub(app.symbols.width() + depth * TREE_INDENT);
+        let scroll = (selection == path).then(|| app.selected_scroll());
+        fit_row(spans, flex, available, scroll)
+    };
+
+    let session_items = sessions
         .iter()
         .enumerate()
         .map(|(si, session)| {
             let label = session_label(si);
+            let depth =
+                usize::from(app.group_sessions && tmux::session_group(&session.name).is_some());
 
             let label_span = Span::styled(
                 format!("[{label}] "),
@@ -174,109 +601,476 @@ fn build_tree_items(sessions: &[TmuxSession]) -> Vec<TreeItem<'static, String>>
                 Span::styled("○ ", Style::default().fg(Color::DarkGray))
             };
 
-            let name = Span::styled(
-                session.name.clone(),
-                Style::default().fg(CYAN).add_modifier(Modifier::BOLD),
-            );
+            let name_color = if app.hash_colors {
+                name_color(&session.name)
+            } else {
+                CYAN
+            };
+            let pin = if app.pinned.contains(&session.name) {
+                Span::styled("★ ", Style::default().fg(YELLOW))
+            } else {
+                Span::raw("")
+            };
+            // Renaming in place turns the name into the edit field
+            let editing = match &app.mode {
+                Mode::RenameSession {
+                    target,
+                    input,
+                    error,
+                    inline: true,
+                } if *target == session.name => Some((input, error)),
+                _ => None,
+            };
+            let name = match editing {
+                Some((input, _)) => Span::styled(
+                    format!("{input}█"),
+                    Style::default()
+                        .fg(WHITE)
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                ),
+                None => Span::styled(
+                    session.name.clone(),
+                    Style::default().fg(name_color).add_modifier(Modifier::BOLD),
+                ),
+            };
+
+            let id = if app.show_session_ids {
+                Span::styled(format!(" {}", session.id), Style::default().fg(DIM))
+            } else {
+                Span::raw("")
+            };
 
             let meta = Span::styled(
-                format!("  ({} win)", session.window_count),
+                format!("  ({})", session_meta(session, now)),
                 Style::default().fg(DIM),
             );
 
             let attached_badge = if session.attached {
                 Span::styled("  [attached]", Style::default().fg(GREEN))
+            } else if app.last_attached.as_deref() == Some(session.name.as_str()) {
+                // Where tmxu last attached, after detaching from it
+                Span::styled("  [last]", Style::default().fg(DIM))
             } else {
                 Span::raw("")
             };
 
-            let session_line = Line::from(vec![label_span, status, name, meta, attached_badge]);
-
-            let window_items: Vec<TreeItem<'static, String>> = session
-                .windows
-                .iter()
-                .enumerate()
-                .map(|(wi, window)| {
-                    let win_label =
-                        Span::styled(format!("[{}] ", wi + 1), Style::default().fg(YELLOW));
-                    let summary = TmuxSession::window_summary(window);
-                    let wname = Span::styled(window.name.to_string(), Style::default().fg(WHITE));
-                    let path = Span::styled(format!("  {summary}"), Style::default().fg(DIM));
-                    let window_line = Line::from(vec![win_label, wname, path]);
-
-                    if window.panes.len() > 1 {
-                        let pane_items: Vec<TreeItem<'static, String>> = window
-                            .panes
-                            .iter()
-                            .map(|pane| {
-                                let active_marker = if pane.active { "* " } else { "  " };
-                                let pane_text = format!(
-                                    "{}pane {}: {}  {}",
-                                    active_marker,
-                                    pane.index,
-                                    pane.current_command,
-                                    tmux::shorten_path(&pane.current_path),
-                                );
-                                TreeItem::new_leaf(
-                                    format!("{}", pane.index),
-                                    Span::styled(pane_text, Style::default().fg(DIM)),
-                                )
-                            })
-                            .collect();
-                        TreeItem::new(format!("{}", window.index), window_line, pane_items)
-                            .expect("unique pane identifiers")
-                    } else {
-                        TreeItem::new_leaf(format!("{}", window.index), window_line)
-                    }
-                })
-                .collect();
+            // A note takes the truncation so the name stays readable
+            let (note, flex) = match app.notes.get(&session.name) {
+                Some(note) => (
+                    Span::styled(
+                        format!("  {note}"),
+                        Style::default().fg(DIM).add_modifier(Modifier::ITALIC),
+                    ),
+                    5,
+                ),
+                None => (Span::raw(""), 3),
+            };
+            // Where the popup would show a refused name's reason
+            let note = match editing {
+                Some((_, Some(error))) => {
+                    Span::styled(format!("  {error}"), Style::default().fg(RED))
+                }
+                _ => note,
+            };
+
+            let session_line = fit(
+                vec![
+                    label_span,
+                    status,
+                    pin,
+                    name,
+                    id,
+                    note,
+                    meta,
+                    attached_badge,
+                ],
+                flex,
+                depth,
+                &[session.name.as_str()],
+            );
+
+            let window_items: Vec<TreeItem<'static, String>> =
+                labeled_windows(&session.windows, app.tmux_indices)
+                    .map(|(number, window)| {
+                        let window_id = format!("{}", window.index);
+                        let win_label =
+                            Span::styled(format!("[{number}] "), Style::default().fg(YELLOW));
+                        let summary =
+                            TmuxSession::window_summary(window, &app.window_format, app.fish_paths);
+                        let wname = Span::styled(
+                            window.label(app.shell_name.as_deref()),
+                            Style::default().fg(WHITE),
+                        );
+                        let path = Span::styled(format!("  {summary}"), Style::default().fg(DIM));
+                        let sync_badge = if window.synchronized {
+                            Span::styled("  [sync]", Style::default().fg(MAGENTA))
+                        } else {
+                            Span::raw("")
+                        };
+                        // A linked window also shows under another session
+                        let linked_badge = if window.linked {
+                            Span::styled("  [linked]", Style::default().fg(CYAN))
+                        } else {
+                            Span::raw("")
+                        };
+                        // Dead panes can be respawned; flag them even while collapsed
+                        let dead_badge = if window.panes.iter().any(|p| p.dead) {
+                            Span::styled("  [dead]", Style::default().fg(RED))
+                        } else {
+                            Span::raw("")
+                        };
+                        let window_line = fit(
+                            vec![win_label, wname, path, sync_badge, linked_badge, dead_badge],
+                            2,
+                            depth + 1,
+                            &[session.name.as_str(), window_id.as_str()],
+                        );
+
+                        if window.panes.len() > 1 || app.always_show_panes {
+                            let pane_items: Vec<TreeItem<'static, String>> = window
+                                .panes
+                                .iter()
+                                .map(|pane| {
+                                    let active_marker = if pane.active { "* " } else { "  " };
+                                    let command = if show_processes {
+                                        let line = pane
+                                            .command_line
+                                            .as_deref()
+                                            .unwrap_or(&pane.current_command);
+                                        format!("[{}] {line}", pane.pid)
+                                    } else {
+                                        pane.display_command()
+                                    };
+                                    let mut pane_text = format!(
+                                        "{}pane {}: {}  {}",
+                                        active_marker,
+                                        pane.index,
+                                        command,
+                                        tmux::shorten_path(&pane.current_path),
+                                    );
+                                    if let Some(title) = pane.display_title() {
+                                        pane_text.push_str(&format!("  \"{title}\""));
+                                    }
+                                    let pane_id = format!("{}", pane.index);
+                                    let pane_color = if pane.dead { RED } else { DIM };
+                                    let usage = match (&pane.usage, app.show_usage) {
+                                        (Some(usage), true) => Span::styled(
+                                            format!("  {}", format_usage(usage)),
+                                            Style::default().fg(DIM).add_modifier(Modifier::DIM),
+                                        ),
+                                        _ => Span::raw(""),
+                                    };
+                                    let pane_line = fit(
+                                        vec![
+                                            Span::styled(
+                                                pane_text,
+                                                Style::default().fg(pane_color),
+                                            ),
+                                            usage,
+                                        ],
+                                        0,
+                                        depth + 2,
+                                        &[
+                                            session.name.as_str(),
+                                            window_id.as_str(),
+                                            pane_id.as_str(),
+                                        ],
+                                    );
+                                    TreeItem::new_leaf(pane_id, pane_line)
+                                })
+                                .collect();
+                            TreeItem::new(window_id, window_line, pane_items)
+                        } else {
+                            Ok(TreeItem::new_leaf(window_id, window_line))
+                        }
+                    })
+                    .collect::<std::io::Result<_>>()?;
 
             TreeItem::new(session.name.clone(), session_line, window_items)
-                .expect("unique window identifiers")
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    if !app.group_sessions {
+        return Ok(session_items);
+    }
+
+    // Fold sessions sharing a name prefix under one group node, placed where
+    // its first member would have been. Labels stay attached to each session.
+    let mut nodes: Vec<(Option<&str>, Vec<TreeItem<'static, String>>)> = Vec::new();
+    for (session, item) in sessions.iter().zip(session_items) {
+        let group = tmux::session_group(&session.name);
+        match group.and_then(|g| nodes.iter_mut().find(|(n, _)| *n == Some(g))) {
+            Some((_, members)) => members.push(item),
+            None => nodes.push((group, vec![item])),
+        }
+    }
+
+    nodes
+        .into_iter()
+        .map(|(group, mut members)| match group {
+            Some(prefix) => {
+                let group_line = Line::from(vec![
+                    Span::styled(
+                        prefix.to_string(),
+                        Style::default().fg(MAGENTA).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        format!("  ({} sessions)", members.len()),
+                        Style::default().fg(DIM),
+                    ),
+                ]);
+                TreeItem::new(group_id(prefix), group_line, members)
+            }
+            None => Ok(members.remove(0)),
         })
         .collect()
 }
 
+/// "3 win, 8 panes", plus how long the session has been idle once that's
+/// worth showing. Panes are counted from the parsed rows, not asked of tmux.
+fn session_meta(session: &TmuxSession, now: u64) -> String {
+    let panes = session.pane_count();
+    let mut meta = format!(
+        "{} win, {panes} {}",
+        session.window_count,
+        if panes == 1 { "pane" } else { "panes" }
+    );
+    let idle = session.idle_secs(now);
+    if session.activity > 0 && idle >= IDLE_DISPLAY_THRESHOLD {
+        meta.push_str(&format!(", idle {}", format_age(idle)));
+    }
+    meta
+}
+
+/// Plaintext rendering of the session tree for `--watch`: the same rows as
+/// `build_tree_items`, fully expanded, without styling or width fitting
+pub fn render_plain(
+    sessions: &[TmuxSession],
+    window_format: &str,
+    fish_paths: bool,
+    all_panes: bool,
+) -> String {
+    let now = unix_now();
+    let mut out = String::new();
+    for (si, session) in sessions.iter().enumerate() {
+        let status = if session.attached { '●' } else { '○' };
+        let meta = session_meta(session, now);
+        let badge = if session.attached { "  [attached]" } else { "" };
+        out.push_str(&format!(
+            "[{}] {status} {}  ({meta}){badge}\n",
+            session_label(si),
+            session.name
+        ));
+
+        for (number, window) in labeled_windows(&session.windows, false) {
+            let summary = TmuxSession::window_summary(window, window_format, fish_paths);
+            let sync = if window.synchronized { "  [sync]" } else { "" };
+            let linked = if window.linked { "  [linked]" } else { "" };
+            let dead = if window.panes.iter().any(|p| p.dead) {
+                "  [dead]"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "  [{number}] {}  {summary}{sync}{linked}{dead}\n",
+                window.display_name()
+            ));
+            if window.panes.len() > 1 || all_panes {
+                for pane in &window.panes {
+                    let active_marker = if pane.active { "* " } else { "  " };
+                    out.push_str(&format!(
+                        "    {active_marker}pane {}: {}  {}\n",
+                        pane.index,
+                        pane.display_command(),
+                        tmux::shorten_path(&pane.current_path),
+                    ));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Braille spinner shown in the status bar while tmux is slow to answer
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
 fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
-    let flash_line = if let Some(ref flash) = app.flash {
-        Line::from(Span::styled(
-            format!("  {}", flash.text),
-            Style::default().fg(YELLOW),
-        ))
+    let message = if let Some(flash) = app.flash.front() {
+        let color = match flash.level {
+            Level::Info => YELLOW,
+            Level::Success => GREEN,
+            Level::Error => RED,
+        };
+        Span::styled(flash.text.clone(), Style::default().fg(color))
     } else {
-        Line::from("")
+        Span::styled(session_summary(&app.sessions), Style::default().fg(DIM))
+    };
+    // A slow tmux gets a spinner in the margin so tmxu doesn't look frozen
+    let margin = match app.spinner() {
+        Some(frame) => Span::styled(
+            format!("{} ", SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]),
+            Style::default().fg(CYAN),
+        ),
+        None => Span::raw("  "),
     };
+    let flash_line = Line::from(vec![margin, message]);
+
+    let mut keybinds = vec![Span::raw("  ")];
+    for (i, (key, action)) in keymap(app.selection().len()).iter().enumerate() {
+        if i > 0 {
+            keybinds.push(Span::raw("  "));
+        }
+        keybinds.push(Span::styled(*key, Style::default().fg(CYAN)));
+        keybinds.push(Span::styled(format!(":{action}"), Style::default().fg(DIM)));
+    }
+    let keybinds = Line::from(keybinds);
+
+    let mut lines = vec![flash_line];
+    if app.inside_tmux {
+        lines.push(Line::from(Span::styled(
+            "  inside tmux — Enter will switch-client",
+            Style::default().fg(DIM),
+        )));
+    }
+    lines.push(keybinds);
 
-    let keybinds = Line::from(vec![
-        Span::styled("  a-z", Style::default().fg(CYAN)),
-        Span::styled(":select  ", Style::default().fg(DIM)),
-        Span::styled("A-Z", Style::default().fg(CYAN)),
-        Span::styled(":open  ", Style::default().fg(DIM)),
-        Span::styled("1-9", Style::default().fg(CYAN)),
-        Span::styled(":window  ", Style::default().fg(DIM)),
-        Span::styled("Enter", Style::default().fg(CYAN)),
-        Span::styled(":attach  ", Style::default().fg(DIM)),
-        Span::styled("n", Style::default().fg(CYAN)),
-        Span::styled(":new  ", Style::default().fg(DIM)),
-        Span::styled("d", Style::default().fg(CYAN)),
-        Span::styled(":kill  ", Style::default().fg(DIM)),
-        Span::styled("q", Style::default().fg(CYAN)),
-        Span::styled(":quit", Style::default().fg(DIM)),
-    ]);
-
-    let status = Paragraph::new(vec![flash_line, keybinds]).block(
+    // The border faces the tree
+    let status_top = app.layout == StatusPosition::Top;
+    let status = Paragraph::new(lines).block(
         Block::default()
-            .borders(Borders::TOP)
+            .borders(if status_top {
+                Borders::BOTTOM
+            } else {
+                Borders::TOP
+            })
             .border_style(Style::default().fg(DIM)),
     );
     frame.render_widget(status, area);
+
+    if app.show_clock && area.height > 1 {
+        let now = unix_now();
+        let mut clock = clock_time(now, app.utc_offset);
+        if let Some(started) = app.server_started {
+            clock.push_str(&format!(
+                " · up {}",
+                format_age(now.saturating_sub(started))
+            ));
+        }
+        // Right-aligned on the flash line, inside any top border
+        let row = Rect {
+            y: area.y + u16::from(!status_top),
+            height: 1,
+            ..area
+        };
+        let clock = Paragraph::new(Span::styled(format!("{clock} "), Style::default().fg(DIM)))
+            .alignment(Alignment::Right);
+        frame.render_widget(clock, row);
+    }
+}
+
+/// Wall-clock "HH:MM" for Unix time `now` at `utc_offset` seconds from UTC
+fn clock_time(now: u64, utc_offset: i64) -> String {
+    let local = now as i64 + utc_offset;
+    let minutes = local.div_euclid(60).rem_euclid(24 * 60);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Local time's offset from UTC in seconds, from `date +%z` (0 if unavailable)
+pub fn local_utc_offset() -> i64 {
+    Command::new("date")
+        .arg("+%z")
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .and_then(|s| parse_utc_offset(&s))
+        .unwrap_or(0)
 }
 
-fn draw_input_popup(frame: &mut Frame, title: &str, input: &str) {
+/// "+0530" / "-0800" to seconds
+fn parse_utc_offset(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (sign, digits) = if let Some(digits) = s.strip_prefix('+') {
+        (1, digits)
+    } else {
+        (-1, s.strip_prefix('-')?)
+    };
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Status bar key hints for a selection `depth` deep: a session (1), a
+/// window (2) or a pane (3); anything else gets the general keys
+fn keymap(depth: usize) -> &'static [(&'static str, &'static str)] {
+    match depth {
+        2 => &[
+            ("Enter", "attach"),
+            ("Shift+↑/↓", "reorder"),
+            ("l/h", "expand/collapse"),
+            (",", "actions"),
+            ("q", "quit"),
+        ],
+        3 => &[
+            ("Enter", "attach"),
+            ("!", "send"),
+            (",", "actions"),
+            ("q", "quit"),
+        ],
+        1 => &[
+            ("Enter", "attach"),
+            ("r", "rename"),
+            ("d", "kill"),
+            ("*", "pin"),
+            (",", "actions"),
+            ("q", "quit"),
+        ],
+        _ => &[
+            ("a-z", "select"),
+            ("A-Z", "open"),
+            ("1-9", "window"),
+            ("Enter", "attach"),
+            ("n", "new"),
+            ("d", "kill"),
+            (",", "actions"),
+            ("q", "quit"),
+        ],
+    }
+}
+
+/// Glance summary of the session list, e.g. "5 sessions · 14 windows · 2 attached"
+pub fn session_summary(sessions: &[TmuxSession]) -> String {
+    let windows: usize = sessions.iter().map(|s| s.windows.len()).sum();
+    let attached = sessions.iter().filter(|s| s.attached).count();
+    format!(
+        "{} {} · {} {} · {} attached",
+        sessions.len(),
+        if sessions.len() == 1 {
+            "session"
+        } else {
+            "sessions"
+        },
+        windows,
+        if windows == 1 { "window" } else { "windows" },
+        attached,
+    )
+}
+
+/// Text prompt with an optional key hint on the bottom border and an
+/// `error` explaining why the input was refused
+fn draw_input_popup(
+    frame: &mut Frame,
+    title: &str,
+    input: &str,
+    hint: Option<&str>,
+    error: Option<&str>,
+) {
     let area = centered_rect(50, 5, frame.area());
     frame.render_widget(Clear, area);
 
-    let text = vec![
+    let mut text = vec![
         Line::from(""),
         Line::from(vec![
             Span::styled("  > ", Style::default().fg(CYAN)),
@@ -284,43 +1078,374 @@ fn draw_input_popup(frame: &mut Frame, title: &str, input: &str) {
             Span::styled("█", Style::default().fg(CYAN)), // cursor
         ]),
     ];
+    if let Some(error) = error {
+        text.push(Line::from(Span::styled(
+            format!("  {error}"),
+            Style::default().fg(RED),
+        )));
+    }
+
+    let mut block = Block::bordered()
+        .title(format!(" {title} "))
+        .title_alignment(Alignment::Center)
+        .border_style(Style::default().fg(MAGENTA));
+    if let Some(hint) = hint {
+        block = block.title_bottom(Line::from(format!(" {hint} ")).centered());
+    }
+    let popup = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
+    frame.render_widget(popup, area);
+}
+
+/// What killing a session takes with it, e.g. "3 windows, 7 panes will be lost"
+fn blast_radius(windows: usize, panes: usize) -> String {
+    format!(
+        "{windows} {}, {panes} {} will be lost",
+        if windows == 1 { "window" } else { "windows" },
+        if panes == 1 { "pane" } else { "panes" }
+    )
+}
+
+/// Yes/no confirmation popup: "{prompt} '{subject}'? [y/N]", with an
+/// optional warning `detail` line below
+fn draw_confirm_popup(
+    frame: &mut Frame,
+    title: &str,
+    prompt: &str,
+    subject: Option<&str>,
+    detail: Option<&str>,
+) {
+    let height = if detail.is_some() { 6 } else { 5 };
+    let area = centered_rect(50, height, frame.area());
+    frame.render_widget(Clear, area);
+
+    let mut question = vec![Span::styled(
+        format!("  {prompt}"),
+        Style::default().fg(WHITE),
+    )];
+    if let Some(subject) = subject {
+        question.push(Span::styled(
+            format!(" '{subject}'"),
+            Style::default().fg(YELLOW).add_modifier(Modifier::BOLD),
+        ));
+    }
+    question.push(Span::styled("? ", Style::default().fg(WHITE)));
+    question.push(Span::styled("[y/N]", Style::default().fg(CYAN)));
+
+    let mut text = vec![Line::from(""), Line::from(question)];
+    if let Some(detail) = detail {
+        text.push(Line::from(Span::styled(
+            format!("  {detail}"),
+            Style::default().fg(RED),
+        )));
+    }
 
     let popup = Paragraph::new(text)
         .block(
             Block::bordered()
                 .title(format!(" {title} "))
                 .title_alignment(Alignment::Center)
-                .border_style(Style::default().fg(MAGENTA)),
+                .border_style(Style::default().fg(RED)),
         )
         .wrap(Wrap { trim: false });
     frame.render_widget(popup, area);
 }
 
-fn draw_confirm_popup(frame: &mut Frame, target: &str) {
-    let area = centered_rect(50, 5, frame.area());
+/// Bordered red popup for an error too long for the status bar
+fn draw_error_popup(frame: &mut Frame, message: &str, scroll: u16) {
+    let lines: Vec<Line> = message
+        .lines()
+        .map(|line| Line::from(Span::styled(format!(" {line}"), Style::default().fg(WHITE))))
+        .collect();
+    // Room for the text plus borders and the hint, within the screen
+    let height = (lines.len() as u16 + 4).min(frame.area().height.saturating_sub(2));
+    let area = centered_rect(80, height, frame.area());
     frame.render_widget(Clear, area);
 
-    let text = vec![
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("  Kill session ", Style::default().fg(WHITE)),
-            Span::styled(
-                format!("'{target}'"),
-                Style::default().fg(YELLOW).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("? ", Style::default().fg(WHITE)),
-            Span::styled("[y/N]", Style::default().fg(CYAN)),
-        ]),
-    ];
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::bordered()
+                .title(" Error ")
+                .title_alignment(Alignment::Center)
+                .title_bottom(Line::from(" j/k: scroll · any key: close ").centered())
+                .border_style(Style::default().fg(RED)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    frame.render_widget(popup, area);
+}
 
-    let popup = Paragraph::new(text)
+fn draw_environment_popup(
+    frame: &mut Frame,
+    session: &str,
+    vars: &[(String, String)],
+    scroll: u16,
+) {
+    let lines: Vec<Line> = if vars.is_empty() {
+        vec![Line::from(Span::styled(
+            " (empty)",
+            Style::default().fg(DIM),
+        ))]
+    } else {
+        vars.iter()
+            .map(|(key, value)| match key.strip_prefix('-') {
+                // Removed from the global environment for this session
+                Some(key) => Line::from(vec![
+                    Span::styled(format!(" {key}"), Style::default().fg(DIM)),
+                    Span::styled(" (unset)", Style::default().fg(RED)),
+                ]),
+                None => Line::from(vec![
+                    Span::styled(format!(" {key}"), Style::default().fg(CYAN)),
+                    Span::styled("=", Style::default().fg(DIM)),
+                    Span::styled(value.clone(), Style::default().fg(WHITE)),
+                ]),
+            })
+            .collect()
+    };
+    let height = (lines.len() as u16 + 2).min(frame.area().height.saturating_sub(2));
+    let area = centered_rect(80, height, frame.area());
+    frame.render_widget(Clear, area);
+
+    let popup = Paragraph::new(lines)
         .block(
             Block::bordered()
-                .title(" Confirm Kill ")
+                .title(format!(" Environment of '{session}' "))
                 .title_alignment(Alignment::Center)
-                .border_style(Style::default().fg(Color::Red)),
+                .title_bottom(Line::from(" j/k: scroll · Esc: close ").centered())
+                .border_style(Style::default().fg(MAGENTA)),
         )
-        .wrap(Wrap { trim: false });
+        .scroll((scroll, 0));
+    frame.render_widget(popup, area);
+}
+
+fn draw_preview_popup(frame: &mut Frame, target: &str, text: &str, lines: u16) {
+    use ansi_to_tui::IntoText;
+
+    let height = (lines + 2).min(frame.area().height.saturating_sub(2));
+    let area = centered_rect(90, height, frame.area());
+    frame.render_widget(Clear, area);
+
+    // Keep the newest output in view when the popup is shorter than the capture
+    let rows = text.lines().count() as u16;
+    let scroll = rows.saturating_sub(height.saturating_sub(2));
+    // Escape sequences tui can't parse still leave the text readable raw
+    let styled = text
+        .into_text()
+        .unwrap_or_else(|_| Text::raw(text.to_string()));
+    let popup = Paragraph::new(styled)
+        .style(Style::default().fg(WHITE))
+        .block(
+            Block::bordered()
+                .title(format!(" {target} · last {lines} lines "))
+                .title_alignment(Alignment::Center)
+                .title_bottom(Line::from(" +/-: more/fewer lines · any key: close ").centered())
+                .border_style(Style::default().fg(CYAN)),
+        )
+        .scroll((scroll, 0));
+    frame.render_widget(popup, area);
+}
+
+fn draw_search_popup(frame: &mut Frame, sessions: &[TmuxSession], query: &str, selected: usize) {
+    let found = tmux::search_panes(sessions, query);
+    let selected = selected.min(found.len().saturating_sub(1));
+
+    // Input line, blank line and borders take 4 rows; the rest lists matches
+    let height = (found.len() as u16 + 4)
+        .max(5)
+        .min(frame.area().height.saturating_sub(2));
+    let area = centered_rect(80, height, frame.area());
+    frame.render_widget(Clear, area);
+    let visible = usize::from(height.saturating_sub(4)).max(1);
+    let offset = (selected + 1).saturating_sub(visible);
+
+    let mut text = vec![
+        Line::from(vec![
+            Span::styled(" > ", Style::default().fg(CYAN)),
+            Span::styled(query, Style::default().fg(WHITE)),
+            Span::styled("█", Style::default().fg(CYAN)), // cursor
+        ]),
+        Line::from(""),
+    ];
+    if found.is_empty() {
+        text.push(Line::from(Span::styled(
+            " no matching panes",
+            Style::default().fg(DIM),
+        )));
+    }
+    text.extend(
+        found
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(visible)
+            .map(|(i, m)| {
+                // Only the field that ranked the pane shows its matched letters
+                let spans_for = |field: MatchField, text: &str, color: Color| {
+                    let query = if m.field == Some(field) { query } else { "" };
+                    highlight_matches(text, query, Style::default().fg(color))
+                };
+                let mut spans = vec![Span::raw(" ")];
+                spans.extend(spans_for(MatchField::Session, &m.session, YELLOW));
+                spans.push(Span::styled(
+                    format!(":{}.{}  ", m.window, m.pane),
+                    Style::default().fg(YELLOW),
+                ));
+                spans.extend(spans_for(MatchField::Command, &m.command, WHITE));
+                spans.push(Span::raw("  "));
+                spans.extend(spans_for(
+                    MatchField::Path,
+                    &tmux::shorten_path(&m.path),
+                    DIM,
+                ));
+                let line = Line::from(spans);
+                if i == selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            }),
+    );
+
+    let popup = Paragraph::new(text).block(
+        Block::bordered()
+            .title(format!(" Find Pane ({}) ", found.len()))
+            .title_alignment(Alignment::Center)
+            .title_bottom(
+                Line::from(" ↑/↓: pick · Enter: go · Alt+Enter: attach · Esc: close ").centered(),
+            )
+            .border_style(Style::default().fg(MAGENTA)),
+    );
+    frame.render_widget(popup, area);
+}
+
+/// Split `text` into spans in `style`, with the characters `query`
+/// fuzzy-matches picked out
+fn highlight_matches(text: &str, query: &str, style: Style) -> Vec<Span<'static>> {
+    let positions = tmux::fuzzy_match(text, query)
+        .map(|(_, positions)| positions)
+        .unwrap_or_default();
+    let hit_style = Style::default().fg(CYAN).add_modifier(Modifier::BOLD);
+
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_hit = false;
+    for (i, c) in text.chars().enumerate() {
+        let hit = positions.contains(&i);
+        if hit != run_hit && !run.is_empty() {
+            let style = if run_hit { hit_style } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), style));
+        }
+        run_hit = hit;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, if run_hit { hit_style } else { style }));
+    }
+    spans
+}
+
+fn draw_menu_popup(frame: &mut Frame, selected: usize) {
+    // A blank line and borders take 4 rows; on a short terminal the list
+    // scrolls to keep the selection in view
+    let height = (MenuItem::ALL.len() as u16 + 4).min(frame.area().height.saturating_sub(2));
+    let area = centered_rect(44, height, frame.area());
+    frame.render_widget(Clear, area);
+    let visible = usize::from(height.saturating_sub(4)).max(1);
+    let offset = (selected + 1).saturating_sub(visible);
+
+    let mut text = vec![Line::from("")];
+    text.extend(
+        MenuItem::ALL
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(visible)
+            .map(|(i, item)| {
+                let line = Line::from(vec![
+                    Span::styled(format!("  {}  ", item.key()), Style::default().fg(CYAN)),
+                    Span::styled(item.label(), Style::default().fg(WHITE)),
+                ]);
+                if i == selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            }),
+    );
+
+    let popup = Paragraph::new(text).block(
+        Block::bordered()
+            .title(" Actions ")
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(" key or ↑/↓ + Enter · Esc: close ").centered())
+            .border_style(Style::default().fg(MAGENTA)),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn draw_move_popup(
+    frame: &mut Frame,
+    sessions: &[&TmuxSession],
+    src: &str,
+    link: bool,
+    pending: Option<char>,
+) {
+    let pending = pending.map(|first| first.to_ascii_uppercase());
+
+    // Past what fits on screen, entries flow into further columns
+    let rows = sessions
+        .len()
+        .min(usize::from(frame.area().height.saturating_sub(6)))
+        .max(1);
+    let width = if sessions.len() > rows { 90 } else { 50 };
+    let area = centered_rect(width, rows as u16 + 4, frame.area());
+    frame.render_widget(Clear, area);
+
+    let entries: Vec<(String, &str)> = sessions
+        .iter()
+        .enumerate()
+        .map(|(si, session)| (session_label(si), session.name.as_str()))
+        .collect();
+    let column_width = entries
+        .iter()
+        .map(|(label, name)| label.len() + name.chars().count() + 5)
+        .max()
+        .unwrap_or(0);
+
+    let mut text = vec![Line::from("")];
+    for row in 0..rows {
+        let mut spans = Vec::new();
+        for (label, name) in entries.iter().skip(row).step_by(rows) {
+            // Once a first letter is typed, only the labels it starts stay lit
+            let lit = pending.is_none_or(|first| label.starts_with(first));
+            let style = |color: Color| Style::default().fg(if lit { color } else { DIM });
+            spans.push(Span::styled(
+                format!("  [{label}] "),
+                style(YELLOW).add_modifier(Modifier::BOLD),
+            ));
+            let pad = column_width - label.len() - name.chars().count() - 5;
+            spans.push(Span::styled(
+                format!("{name}{}", " ".repeat(pad)),
+                style(CYAN),
+            ));
+        }
+        text.push(Line::from(spans));
+    }
+
+    let hint = match pending {
+        Some(first) => format!(" {first}…: second letter · Enter: [{first}] · Esc: cancel "),
+        None => " letter: pick · Esc: cancel ".to_string(),
+    };
+    let popup = Paragraph::new(text).block(
+        Block::bordered()
+            .title(if link {
+                format!(" Link '{src}' into ")
+            } else {
+                format!(" Move '{src}' to ")
+            })
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(hint).centered())
+            .border_style(Style::default().fg(MAGENTA)),
+    );
     frame.render_widget(popup, area);
 }
 
@@ -342,3 +1467,148 @@ fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
 
     horizontal[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_matches() {
+        let style = Style::default().fg(WHITE);
+        let text: Vec<String> = highlight_matches("cargo", "co", style)
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect();
+        assert_eq!(text, vec!["c", "arg", "o"]);
+        assert_eq!(highlight_matches("cargo", "", style).len(), 1);
+        assert_eq!(highlight_matches("cargo", "xyz", style).len(), 1);
+    }
+
+    #[test]
+    fn test_blast_radius() {
+        assert_eq!(blast_radius(3, 7), "3 windows, 7 panes will be lost");
+        assert_eq!(blast_radius(1, 1), "1 window, 1 pane will be lost");
+    }
+
+    #[test]
+    fn test_keymap_follows_selection_depth() {
+        let has = |depth, key| keymap(depth).iter().any(|(k, _)| *k == key);
+        assert!(has(0, "n"));
+        assert!(has(1, "r"));
+        assert!(has(2, "Shift+↑/↓") && !has(2, "r"));
+        assert!(has(3, "!") && !has(3, "Shift+↑/↓"));
+    }
+
+    #[test]
+    fn test_menu_keys_leave_navigation_free() {
+        let keys: Vec<char> = MenuItem::ALL.iter().map(|item| item.key()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!keys[i + 1..].contains(key), "'{key}' is used twice");
+        }
+        // j/k move through the menu; q and , close it
+        assert!(!keys.iter().any(|k| "jkq,".contains(*k)));
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        assert_eq!(truncate_middle("short", 10), "short");
+        assert_eq!(truncate_middle("very-long-session-name", 10), "very-…name");
+        assert_eq!(truncate_middle("abc", 1), "…");
+        assert_eq!(truncate_middle("abc", 0), "");
+        // Wide chars take two columns each
+        assert_eq!(truncate_middle("日本語のセッション", 9), "日本…ョン");
+        assert_eq!(truncate_middle("日本語", 6), "日本語");
+    }
+
+    #[test]
+    fn test_to_ansi() {
+        assert_eq!(to_ansi(Color::Rgb(140, 20, 200)), Color::Magenta);
+        assert_eq!(to_ansi(Color::Rgb(250, 250, 250)), Color::White);
+        assert_eq!(to_ansi(Color::Rgb(10, 10, 10)), Color::Black);
+        assert_eq!(to_ansi(Color::Cyan), Color::Cyan);
+    }
+
+    #[test]
+    fn test_labeled_windows() {
+        let window = |index| TmuxWindow {
+            index,
+            name: format!("w{index}"),
+            active: false,
+            synchronized: false,
+            activity: 0,
+            linked: false,
+            panes: Vec::new(),
+        };
+        let windows = [window(4), window(1), window(7)];
+
+        let labels = |tmux_indices| {
+            labeled_windows(&windows, tmux_indices)
+                .map(|(n, w)| (n, w.index))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(false), vec![(1, 4), (2, 1), (3, 7)]);
+        assert_eq!(labels(true), vec![(4, 4), (1, 1), (7, 7)]);
+    }
+
+    #[test]
+    fn test_session_label() {
+        assert_eq!(session_label(0), "A");
+        assert_eq!(session_label(25), "Z");
+        assert_eq!(session_label(26), "AA");
+        assert_eq!(session_label(27), "AB");
+        assert_eq!(session_label(52), "BA");
+        assert_eq!(session_label(78), "CA");
+        assert_eq!(session_label(104), "EA");
+        assert_eq!(session_label(467), "ZZ");
+        assert_eq!(session_label(468), "?");
+        assert_eq!(session_index("ab"), Some(27));
+        assert_eq!(session_index("da"), None);
+        assert_eq!(session_index("?"), None);
+    }
+
+    #[test]
+    fn test_every_label_resolves() {
+        let labels: Vec<String> = (0..468).map(session_label).collect();
+        for (idx, label) in labels.iter().enumerate() {
+            assert_eq!(session_index(label), Some(idx), "{label}");
+            assert_eq!(session_index(&label.to_lowercase()), Some(idx), "{label}");
+        }
+        // No two-letter label starts with a letter normal mode binds
+        for label in labels.iter().filter(|l| l.len() == 2) {
+            assert!(!"DGHJKLNQR".contains(&label[..1]), "{label}");
+        }
+    }
+
+    #[test]
+    fn test_format_usage() {
+        let usage = |cpu_tenths, rss_kb| tmux::ProcessUsage { cpu_tenths, rss_kb };
+        assert_eq!(format_usage(&usage(Some(125), 348_160)), "12.5% 340M");
+        assert_eq!(format_usage(&usage(Some(0), 512)), "0.0% 512K");
+        assert_eq!(format_usage(&usage(None, 3 * 1024 * 1024)), "3.0G");
+    }
+
+    #[test]
+    fn test_clock_time() {
+        // 2025-01-01 00:00:00 UTC
+        let midnight = 1_735_689_600;
+        assert_eq!(clock_time(midnight + 14 * 3600 + 5 * 60 + 59, 0), "14:05");
+        assert_eq!(clock_time(midnight, 5 * 3600 + 30 * 60), "05:30");
+        assert_eq!(clock_time(midnight, -8 * 3600), "16:00");
+    }
+
+    #[test]
+    fn test_parse_utc_offset() {
+        assert_eq!(parse_utc_offset("+0530\n"), Some(19_800));
+        assert_eq!(parse_utc_offset("-0800"), Some(-28_800));
+        assert_eq!(parse_utc_offset("+0000"), Some(0));
+        assert_eq!(parse_utc_offset("UTC"), None);
+        assert_eq!(parse_utc_offset(""), None);
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(3 * 60 * 60 + 5), "3h");
+        assert_eq!(format_age(2 * 24 * 60 * 60), "2d");
+    }
+}